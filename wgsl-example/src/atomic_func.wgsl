@@ -0,0 +1,164 @@
+// Single-pass reductions: every invocation folds its element into output[0] atomically.
+@group(0) @binding(0) var<storage, read_write> output: array<atomic<u32>>;
+@group(0) @binding(1) var<storage, read> input_a: array<u32>;
+
+// See `flat_index` of `vec_func.wgsl`.
+fn flat_index(global_id: vec3u, num_workgroups: vec3u) -> u32 {
+  return global_id.x + (global_id.y + global_id.z * num_workgroups.y) * num_workgroups.x * 256u;
+}
+
+@compute @workgroup_size(256)
+fn atomicMax_call(
+  @builtin(global_invocation_id) global_id: vec3u,
+  @builtin(num_workgroups) num_workgroups: vec3u,
+) {
+  let idx = flat_index(global_id, num_workgroups);
+  if idx < arrayLength(&input_a) {
+    atomicMax(&output[0], input_a[idx]);
+  }
+}
+
+// output[0] has to start at 0xFFFFFFFFu
+@compute @workgroup_size(256)
+fn atomicMin_call(
+  @builtin(global_invocation_id) global_id: vec3u,
+  @builtin(num_workgroups) num_workgroups: vec3u,
+) {
+  let idx = flat_index(global_id, num_workgroups);
+  if idx < arrayLength(&input_a) {
+    atomicMin(&output[0], input_a[idx]);
+  }
+}
+
+// Argmax in two stages of one dispatch, output[0] starting at 0u and output[1] at 0xFFFFFFFFu:
+// the first finds the max, the second keeps the lowest index holding it, so ties are stable.
+@compute @workgroup_size(256)
+fn argmaxValue_call(
+  @builtin(global_invocation_id) global_id: vec3u,
+  @builtin(num_workgroups) num_workgroups: vec3u,
+) {
+  let idx = flat_index(global_id, num_workgroups);
+  if idx < arrayLength(&input_a) {
+    atomicMax(&output[0], input_a[idx]);
+  }
+}
+
+@compute @workgroup_size(256)
+fn argmaxIndex_call(
+  @builtin(global_invocation_id) global_id: vec3u,
+  @builtin(num_workgroups) num_workgroups: vec3u,
+) {
+  let idx = flat_index(global_id, num_workgroups);
+  if idx < arrayLength(&input_a) && input_a[idx] == atomicLoad(&output[0]) {
+    atomicMin(&output[1], idx);
+  }
+}
+
+// Sum, min, max and count in one read of the data:
+// output[0] and output[1] are the low word and the carries of the sum like in `wide_sum_func.wgsl`,
+// output[2] the min (starting at 0xFFFFFFFFu), output[3] the max and output[4] the count.
+@compute @workgroup_size(256)
+fn atomicStats_call(
+  @builtin(global_invocation_id) global_id: vec3u,
+  @builtin(num_workgroups) num_workgroups: vec3u,
+) {
+  let idx = flat_index(global_id, num_workgroups);
+  if idx < arrayLength(&input_a) {
+    let value = input_a[idx];
+    let old = atomicAdd(&output[0], value);
+    if old + value < old {
+      atomicAdd(&output[1], 1u);
+    }
+    atomicMin(&output[2], value);
+    atomicMax(&output[3], value);
+    atomicAdd(&output[4], 1u);
+  }
+}
+
+// Counters: output[0] is incremented once for every element the predicate holds for.
+@compute @workgroup_size(256)
+fn countEven_call(
+  @builtin(global_invocation_id) global_id: vec3u,
+  @builtin(num_workgroups) num_workgroups: vec3u,
+) {
+  let idx = flat_index(global_id, num_workgroups);
+  if idx < arrayLength(&input_a) && input_a[idx] % 2u == 0u {
+    atomicAdd(&output[0], 1u);
+  }
+}
+
+@compute @workgroup_size(256)
+fn countOdd_call(
+  @builtin(global_invocation_id) global_id: vec3u,
+  @builtin(num_workgroups) num_workgroups: vec3u,
+) {
+  let idx = flat_index(global_id, num_workgroups);
+  if idx < arrayLength(&input_a) && input_a[idx] % 2u == 1u {
+    atomicAdd(&output[0], 1u);
+  }
+}
+
+// Bit masks: bit idx % 32 of output[idx / 32] is set for every element the predicate holds for.
+// A workgroup covers exactly 8 words, so they are combined in workgroup memory and every
+// word is stored once, instead of 32 invocations contending for it in global memory.
+var<workgroup> mask_words: array<atomic<u32>, 8>;
+
+fn store_mask(workgroup: u32, local: u32, idx: u32, holds: bool) {
+  if local < 8u {
+    atomicStore(&mask_words[local], 0u);
+  }
+  workgroupBarrier();
+
+  if holds {
+    atomicOr(&mask_words[local / 32u], 1u << (idx % 32u));
+  }
+  workgroupBarrier();
+
+  let word = workgroup * 8u + local;
+  if local < 8u && word < arrayLength(&output) {
+    atomicStore(&output[word], atomicLoad(&mask_words[local]));
+  }
+}
+
+@compute @workgroup_size(256)
+fn maskEven_call(
+  @builtin(global_invocation_id) global_id: vec3u,
+  @builtin(num_workgroups) num_workgroups: vec3u,
+  @builtin(local_invocation_index) local: u32,
+) {
+  let idx = flat_index(global_id, num_workgroups);
+  store_mask(idx / 256u, local, idx, idx < arrayLength(&input_a) && input_a[idx] % 2u == 0u);
+}
+
+@compute @workgroup_size(256)
+fn maskOdd_call(
+  @builtin(global_invocation_id) global_id: vec3u,
+  @builtin(num_workgroups) num_workgroups: vec3u,
+  @builtin(local_invocation_index) local: u32,
+) {
+  let idx = flat_index(global_id, num_workgroups);
+  store_mask(idx / 256u, local, idx, idx < arrayLength(&input_a) && input_a[idx] % 2u == 1u);
+}
+
+// There is no atomic f32 add: output[0] holds the bits of the f32 sum, and every invocation
+// retries a compare-exchange of the bits until nobody else changed them in between.
+// The order of the additions, and so the rounding of the sum, differs from run to run.
+@compute @workgroup_size(256)
+fn atomicSumF32_call(
+  @builtin(global_invocation_id) global_id: vec3u,
+  @builtin(num_workgroups) num_workgroups: vec3u,
+) {
+  let idx = flat_index(global_id, num_workgroups);
+  if idx < arrayLength(&input_a) {
+    let value = bitcast<f32>(input_a[idx]);
+    var old = atomicLoad(&output[0]);
+    loop {
+      let sum = bitcast<u32>(bitcast<f32>(old) + value);
+      let result = atomicCompareExchangeWeak(&output[0], old, sum);
+      if result.exchanged {
+        break;
+      }
+      old = result.old_value;
+    }
+  }
+}
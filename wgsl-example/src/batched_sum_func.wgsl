@@ -0,0 +1,43 @@
+// Sums of many vectors packed one after another into input_a, in one dispatch:
+// workgroup i sums input_a[offsets[i]..offsets[i + 1]] into output[i]. Past the workgroups
+// of one row the grid continues along y and z like `GpuConsts::plan_dispatch` lays it out,
+// the workgroups it has past the last vector do nothing.
+@group(0) @binding(0) var<storage, read_write> output: array<u32>;
+@group(0) @binding(1) var<storage, read> input_a: array<u32>;
+@group(0) @binding(2) var<storage, read> offsets: array<u32>;
+
+var<workgroup> partial: array<u32, 256>;
+
+@compute @workgroup_size(256)
+fn batchedSum_call(
+  @builtin(workgroup_id) workgroup_id: vec3u,
+  @builtin(num_workgroups) num_workgroups: vec3u,
+  @builtin(local_invocation_index) local: u32,
+) {
+  let vector = workgroup_id.x + (workgroup_id.y + workgroup_id.z * num_workgroups.y) * num_workgroups.x;
+  // The same for the whole workgroup, so the barriers below stay in uniform control flow
+  if vector >= arrayLength(&output) {
+    return;
+  }
+  let start = offsets[vector];
+  let end = offsets[vector + 1u];
+
+  // Every invocation first sums a strided part of the vector on its own
+  var value = 0u;
+  for (var i = start + local; i < end; i = i + 256u) {
+    value = value + input_a[i];
+  }
+  partial[local] = value;
+  workgroupBarrier();
+
+  for (var stride = 128u; stride > 0u; stride = stride / 2u) {
+    if local < stride {
+      partial[local] += partial[local + stride];
+    }
+    workgroupBarrier();
+  }
+
+  if local == 0u {
+    output[vector] = partial[0];
+  }
+}
@@ -0,0 +1,179 @@
+use std::{env, fs::File, io::Write, process, time::Duration};
+
+use wgsl_example::*;
+
+const USAGE: &str =
+    "usage: bench [--op add|sum|optimized_sum] [--sizes 1k,1m] [--runs 10] [--backend vulkan] [--csv out.csv]";
+
+struct Args {
+    op: Op,
+    sizes: Vec<usize>,
+    runs: usize,
+    backends: wgpu::Backends,
+    csv: Option<String>,
+}
+
+// Accepts plain numbers and `k`/`m` suffixes, like `1000`, `1k` or `1m`.
+fn parse_size(size: &str) -> Result<usize, String> {
+    let size = size.trim().to_lowercase();
+    let (digits, multiplier) = match size.strip_suffix('k') {
+        Some(digits) => (digits, 1_000),
+        None => match size.strip_suffix('m') {
+            Some(digits) => (digits, 1_000_000),
+            None => (size.as_str(), 1),
+        },
+    };
+
+    digits
+        .parse::<usize>()
+        .map(|n| n * multiplier)
+        .map_err(|_| format!("invalid size `{}`", size))
+}
+
+fn parse_args() -> Result<Args, String> {
+    let mut args = Args {
+        op: Op::Sum,
+        sizes: vec![1_000],
+        runs: 1,
+        backends: wgpu::Backends::all(),
+        csv: None,
+    };
+
+    let mut iter = env::args().skip(1);
+    while let Some(flag) = iter.next() {
+        let value = iter
+            .next()
+            .ok_or_else(|| format!("missing value for `{}`", flag))?;
+
+        match flag.as_str() {
+            "--op" => args.op = value.parse()?,
+            "--sizes" => args.sizes = value.split(',').map(parse_size).collect::<Result<_, _>>()?,
+            "--runs" => {
+                args.runs = value
+                    .parse()
+                    .ok()
+                    .filter(|&runs| runs > 0)
+                    .ok_or_else(|| format!("invalid number of runs `{}`", value))?
+            }
+            "--backend" => args.backends = wgpu::util::parse_backends_from_comma_list(&value),
+            "--csv" => args.csv = Some(value),
+            _ => return Err(format!("unknown argument `{}`", flag)),
+        }
+    }
+
+    Ok(args)
+}
+
+fn micros(time: Option<Duration>) -> String {
+    time.map_or("-".to_string(), |time| {
+        format!("{:.2e}", time.as_secs_f64() * 1e6)
+    })
+}
+
+fn csv_micros(time: Option<Duration>) -> String {
+    time.map_or(String::new(), |time| (time.as_secs_f64() * 1e6).to_string())
+}
+
+fn main() {
+    let args = parse_args().unwrap_or_else(|err| {
+        eprintln!("{}\n{}", err, USAGE);
+        process::exit(2);
+    });
+
+    let options = GpuOptions {
+        backends: args.backends,
+        ..Default::default()
+    };
+
+    let gpu = match pollster::block_on(GpuConsts::initialaze_with(args.op.shader_file(), &options))
+    {
+        Ok(gpu) => {
+            let info = gpu.adapter_info();
+            println!(
+                "Adapter: {} ({:?}, {:?})",
+                info.name, info.backend, info.device_type
+            );
+            gpu.warmup(args.op.entry_point());
+            Some(gpu)
+        }
+        Err(err) => {
+            println!("No GPU available ({}), running on the CPU only", err);
+            None
+        }
+    };
+
+    let results: Vec<BenchResult> = args
+        .sizes
+        .iter()
+        .map(|&len| {
+            // Sizes past the limits of the device are only run on the CPU
+            let gpu = gpu.as_ref().filter(|gpu| {
+                match gpu.can_run(len, args.op.binding_number() as usize) {
+                    Ok(()) => true,
+                    Err(err) => {
+                        println!(
+                            "Size {} doesn't fit the GPU ({}), running it on the CPU only",
+                            len, err
+                        );
+                        false
+                    }
+                }
+            });
+            bench_op(gpu, args.op, &generate_data(len, 0), args.runs)
+        })
+        .collect();
+
+    // Medians, with the standard deviation over `--runs` runs
+    println!("| Op | Size | CPU time (µs) | CPU std dev (µs) | GPU time (µs) | GPU std dev (µs) | Results agree |");
+    println!("| :--- | :---: | :---: | :---: | :---: | :---: | :---: |");
+    for result in &results {
+        println!(
+            "| {:?} | {} | {} | {} | {} | {} | {} |",
+            result.op,
+            result.len,
+            micros(Some(result.cpu.median)),
+            micros(Some(result.cpu.std_dev)),
+            micros(result.gpu.map(|gpu| gpu.median)),
+            micros(result.gpu.map(|gpu| gpu.std_dev)),
+            result
+                .agree
+                .map_or("-".to_string(), |agree| agree.to_string()),
+        );
+    }
+
+    if let Some(path) = &args.csv {
+        let mut file = File::create(path).expect("Error while creating the csv file");
+        writeln!(
+            file,
+            "op,size,runs,cpu_min_us,cpu_median_us,cpu_mean_us,cpu_std_us,gpu_min_us,gpu_median_us,gpu_mean_us,gpu_std_us,agree"
+        )
+        .unwrap();
+        for result in &results {
+            let cpu = [
+                result.cpu.min,
+                result.cpu.median,
+                result.cpu.mean,
+                result.cpu.std_dev,
+            ];
+            let gpu = [
+                result.gpu.map(|gpu| gpu.min),
+                result.gpu.map(|gpu| gpu.median),
+                result.gpu.map(|gpu| gpu.mean),
+                result.gpu.map(|gpu| gpu.std_dev),
+            ];
+            writeln!(
+                file,
+                "{:?},{},{},{},{},{}",
+                result.op,
+                result.len,
+                args.runs,
+                cpu.map(|time| csv_micros(Some(time))).join(","),
+                gpu.map(csv_micros).join(","),
+                result
+                    .agree
+                    .map_or(String::new(), |agree| agree.to_string()),
+            )
+            .unwrap();
+        }
+    }
+}
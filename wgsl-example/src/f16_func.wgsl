@@ -0,0 +1,21 @@
+// Half precision addition, every u32 holds two f16 values (the lower half first). They are
+// converted to f32 for the addition and rounded back to f16 when stored.
+@group(0) @binding(0) var<storage, read_write> input_a: array<u32>;
+@group(0) @binding(1) var<storage, read> input_b: array<u32>;
+
+// See `flat_index` of `vec_func.wgsl`.
+fn flat_index(global_id: vec3u, num_workgroups: vec3u) -> u32 {
+  return global_id.x + (global_id.y + global_id.z * num_workgroups.y) * num_workgroups.x * 256u;
+}
+
+@compute @workgroup_size(256)
+fn vectorAdditionF16_call(
+  @builtin(global_invocation_id) global_id: vec3u,
+  @builtin(num_workgroups) num_workgroups: vec3u,
+) {
+  let idx = flat_index(global_id, num_workgroups);
+  if idx < arrayLength(&input_a) {
+    let sum = unpack2x16float(input_a[idx]) + unpack2x16float(input_b[idx]);
+    input_a[idx] = pack2x16float(sum);
+  }
+}
@@ -0,0 +1,23 @@
+@group(0) @binding(0) var<storage, read_write> output: array<u32>;
+
+// See `flat_index` of `vec_func.wgsl`.
+fn flat_index(global_id: vec3u, num_workgroups: vec3u) -> u32 {
+  return global_id.x + (global_id.y + global_id.z * num_workgroups.y) * num_workgroups.x * 256u;
+}
+
+// 256 iterations of a multiply and an add, 512 FLOPs per element.
+// The result is stored so the loop can't be optimized away.
+@compute @workgroup_size(256)
+fn fmaLoop_call(
+  @builtin(global_invocation_id) global_id: vec3u,
+  @builtin(num_workgroups) num_workgroups: vec3u,
+) {
+  let idx = flat_index(global_id, num_workgroups);
+  if idx < arrayLength(&output) {
+    var x: f32 = f32(output[idx]);
+    for (var i: u32 = 0u; i < 256u; i = i + 1u) {
+      x = x * 0.999 + 0.5;
+    }
+    output[idx] = bitcast<u32>(x);
+  }
+}
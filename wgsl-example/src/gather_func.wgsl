@@ -0,0 +1,20 @@
+// Gather with bindings of different element types: output[i] = values[indices[i]].
+@group(0) @binding(0) var<storage, read_write> output: array<f32>;
+@group(0) @binding(1) var<storage, read> indices: array<u32>;
+@group(0) @binding(2) var<storage, read> values: array<f32>;
+
+// See `flat_index` of `vec_func.wgsl`.
+fn flat_index(global_id: vec3u, num_workgroups: vec3u) -> u32 {
+  return global_id.x + (global_id.y + global_id.z * num_workgroups.y) * num_workgroups.x * 256u;
+}
+
+@compute @workgroup_size(256)
+fn gatherF32_call(
+  @builtin(global_invocation_id) global_id: vec3u,
+  @builtin(num_workgroups) num_workgroups: vec3u,
+) {
+  let idx = flat_index(global_id, num_workgroups);
+  if idx < arrayLength(&output) {
+    output[idx] = values[indices[idx]];
+  }
+}
@@ -0,0 +1,19 @@
+// Generator kernels: the output comes from the invocation index alone, binding 0 is
+// allocated zeroed on the GPU with nothing uploaded, see `Bindings::initialize_generator`.
+@group(0) @binding(0) var<storage, read_write> output: array<u32>;
+
+// See `flat_index` of `vec_func.wgsl`.
+fn flat_index(global_id: vec3u, num_workgroups: vec3u) -> u32 {
+  return global_id.x + (global_id.y + global_id.z * num_workgroups.y) * num_workgroups.x * 256u;
+}
+
+@compute @workgroup_size(256)
+fn squares_call(
+  @builtin(global_invocation_id) global_id: vec3u,
+  @builtin(num_workgroups) num_workgroups: vec3u,
+) {
+  let idx = flat_index(global_id, num_workgroups);
+  if idx < arrayLength(&output) {
+    output[idx] = idx * idx;
+  }
+}
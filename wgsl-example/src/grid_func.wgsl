@@ -0,0 +1,26 @@
+struct Dims {
+  width: u32,
+  height: u32,
+  depth: u32,
+  _pad: u32,
+}
+
+@group(0) @binding(0) var<storage, read_write> output: array<u32>;
+@group(1) @binding(0) var<uniform> dims: Dims;
+
+// Writes 1 + the flat index of every cell, so unvisited cells stay 0.
+@compute @workgroup_size(16, 16, 1)
+fn markCells2d_call(@builtin(global_invocation_id) global_id: vec3u) {
+  if global_id.x < dims.width && global_id.y < dims.height {
+    let idx = global_id.y * dims.width + global_id.x;
+    output[idx] = idx + 1u;
+  }
+}
+
+@compute @workgroup_size(8, 8, 4)
+fn markCells3d_call(@builtin(global_invocation_id) global_id: vec3u) {
+  if global_id.x < dims.width && global_id.y < dims.height && global_id.z < dims.depth {
+    let idx = (global_id.z * dims.height + global_id.y) * dims.width + global_id.x;
+    output[idx] = idx + 1u;
+  }
+}
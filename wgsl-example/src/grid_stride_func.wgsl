@@ -0,0 +1,31 @@
+// Grid-stride sum: each invocation adds up input[idx], input[idx + stride], ... where stride
+// is the number of invocations of the whole dispatch, then folds its sum into output[0].
+struct Params {
+  len: u32,
+  stride: u32,
+  _pad0: u32,
+  _pad1: u32,
+}
+
+@group(0) @binding(0) var<storage, read_write> output: array<atomic<u32>>;
+@group(0) @binding(1) var<storage, read> input: array<u32>;
+@group(1) @binding(0) var<uniform> params: Params;
+
+// See `flat_index` of `vec_func.wgsl`.
+fn flat_index(global_id: vec3u, num_workgroups: vec3u) -> u32 {
+  return global_id.x + (global_id.y + global_id.z * num_workgroups.y) * num_workgroups.x * 256u;
+}
+
+@compute @workgroup_size(256)
+fn gridStrideSum_call(
+  @builtin(global_invocation_id) global_id: vec3u,
+  @builtin(num_workgroups) num_workgroups: vec3u,
+) {
+  var sum = 0u;
+  for (var i = flat_index(global_id, num_workgroups); i < params.len; i = i + params.stride) {
+    sum = sum + input[i];
+  }
+  if sum != 0u {
+    atomicAdd(&output[0], sum);
+  }
+}
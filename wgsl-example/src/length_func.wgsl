@@ -0,0 +1,20 @@
+// Element counts of bindings 0 to 3, passed by `BufCoder` to every entry point that uses
+// `@group(1) @binding(0)`, of the main shader or of a module from `GpuConsts::create_module`. Guarding with them keeps the invocations past
+// the end of a buffer whose length isn't a multiple of the workgroup size from touching it.
+struct Lengths {
+  len0: u32,
+  len1: u32,
+  len2: u32,
+  len3: u32,
+}
+
+@group(0) @binding(0) var<storage, read_write> output: array<u32>;
+@group(1) @binding(0) var<uniform> lengths: Lengths;
+
+@compute @workgroup_size(256)
+fn incrementGuarded_call(@builtin(global_invocation_id) global_id: vec3u) {
+  let idx = global_id.x;
+  if idx < lengths.len0 {
+    output[idx] = output[idx] + 1u;
+  }
+}
@@ -1,8 +1,20 @@
-use std::fs::read_to_string;
+#![cfg_attr(feature = "simd", feature(portable_simd))]
+
+use std::{
+    collections::HashMap,
+    fs::read_to_string,
+    path::Path,
+    str::FromStr,
+    sync::{Arc, Mutex, OnceLock},
+    time::{Duration, Instant},
+};
+
+use rand::{rngs::StdRng, Rng, SeedableRng};
 
 use wgpu::{
-    util::DeviceExt, Adapter, AdapterInfo, BindGroupEntry, Buffer, Device, Instance, Queue,
-    ShaderModule,
+    util::DeviceExt, Adapter, AdapterInfo, BindGroupEntry, BindGroupLayout, Buffer, BufferAddress,
+    BufferUsages, CommandEncoder, ComputePipeline, Device, Instance, QuerySet, Queue, ShaderModule,
+    SubmissionIndex,
 };
 
 // macro_rules! all_files {
@@ -11,11 +23,44 @@ use wgpu::{
 // 	};
 // }
 
+// Logs an `event` of a wgpu object with its id, only with the `trace-resources` feature.
+// Pairing "created"/"reused" with "pooled"/"dropped" by id shows what leaks. Creations
+// also log the label, wgpu can't be asked for it afterwards.
+macro_rules! trace_resource {
+    ($event:literal, $kind:literal, $resource:expr, $label:expr) => {
+        #[cfg(feature = "trace-resources")]
+        tracing::debug!(
+            target: "wgsl_example::resources",
+            kind = $kind,
+            id = ?$resource.global_id(),
+            label = $label.unwrap_or_default(),
+            $event
+        );
+        #[cfg(not(feature = "trace-resources"))]
+        let _ = (&$resource, &$label);
+    };
+    ($event:literal, $kind:literal, $resource:expr) => {
+        #[cfg(feature = "trace-resources")]
+        tracing::debug!(
+            target: "wgsl_example::resources",
+            kind = $kind,
+            id = ?$resource.global_id(),
+            $event
+        );
+        #[cfg(not(feature = "trace-resources"))]
+        let _ = &$resource;
+    };
+}
+
 pub struct Bindings {
     input_output: Vec<u32>,
     shared_memory: Vec<u32>,
     global_memory: Vec<u32>,
     output_vec: Vec<u32>,
+    // Length of binding 0 when it is only allocated on the GPU, see `initialize_generator`
+    generated_len: Option<usize>,
+    // Value every element of a generated binding 0 starts as
+    generated_seed: u32,
 }
 
 impl Bindings {
@@ -25,6 +70,8 @@ impl Bindings {
             shared_memory: <_>::default(),
             global_memory: <_>::default(),
             output_vec: <_>::default(),
+            generated_len: None,
+            generated_seed: 0,
         }
     }
 
@@ -34,6 +81,8 @@ impl Bindings {
             shared_memory,
             global_memory: <_>::default(),
             output_vec: <_>::default(),
+            generated_len: None,
+            generated_seed: 0,
         }
     }
 
@@ -47,9 +96,32 @@ impl Bindings {
             shared_memory,
             global_memory,
             output_vec: <_>::default(),
+            generated_len: None,
+            generated_seed: 0,
         }
     }
 
+    /// `initialize_two` for signed kernels, the GPU gets the same bits as `u32`.
+    pub fn initialize_two_i32(input_output: Vec<i32>, shared_memory: Vec<i32>) -> Self {
+        Bindings::initialize_two(
+            bytemuck::cast_slice(&input_output).to_vec(),
+            bytemuck::cast_slice(&shared_memory).to_vec(),
+        )
+    }
+
+    /// `initialize_three` for signed kernels, the GPU gets the same bits as `u32`.
+    pub fn initialize_three_i32(
+        input_output: Vec<i32>,
+        shared_memory: Vec<i32>,
+        global_memory: Vec<i32>,
+    ) -> Self {
+        Bindings::initialize_three(
+            bytemuck::cast_slice(&input_output).to_vec(),
+            bytemuck::cast_slice(&shared_memory).to_vec(),
+            bytemuck::cast_slice(&global_memory).to_vec(),
+        )
+    }
+
     pub fn initialize_four(
         input_vec: Vec<u32>,
         start: Vec<u32>,
@@ -61,287 +133,4445 @@ impl Bindings {
             shared_memory: start,
             global_memory: end,
             output_vec,
+            generated_len: None,
+            generated_seed: 0,
         }
     }
-}
 
-pub struct BufCoder {
-    staging_buffer: Buffer,
-}
+    /// Binding 0 for kernels that only write their output, e.g. `output[i] = i * i`:
+    /// `len` zeroed elements allocated on the GPU, with nothing uploaded from the host.
+    pub fn initialize_generator(len: usize) -> Self {
+        Bindings {
+            generated_len: Some(len),
+            ..Bindings::initialize_one(Vec::new())
+        }
+    }
 
-impl BufCoder {
-    pub fn initialize(
-        gpu: &GpuConsts,
-        numbers: &mut Bindings,
-        func_name: &str,
-        binding_number: u32,
-    ) -> BufCoder {
-        // Gets the size in bytes of the buffer.
-        let slice_size = numbers.input_output.len() * std::mem::size_of::<u32>();
-        let size = slice_size as wgpu::BufferAddress;
+    /// `initialize_generator` with every element starting as `seed` instead of zero: the
+    /// identity of the reduction accumulating into binding 0, e.g. `u32::MAX` for a min
+    /// or 1 for a product.
+    pub fn initialize_seeded(len: usize, seed: u32) -> Self {
+        Bindings {
+            generated_seed: seed,
+            ..Bindings::initialize_generator(len)
+        }
+    }
 
-        // Instantiates buffer without data.
-        // `usage` of buffer specifies how it can be used:
-        //   `BufferUsages::MAP_READ` allows it to be read (outside the shader).
-        //   `BufferUsages::COPY_DST` allows it to be the destination of the copy.
-        let staging_buffer = gpu.device.create_buffer(&wgpu::BufferDescriptor {
-            label: None,
-            size,
-            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
-            mapped_at_creation: false,
-        });
+    /// Replaces the contents of binding 0, keeping its allocation when `data` fits.
+    /// The next `BufCoder` created from these bindings uploads the new data.
+    pub fn set_input(&mut self, data: &[u32]) {
+        self.input_output.clear();
+        self.input_output.extend_from_slice(data);
+        self.generated_len = None;
+    }
 
-        // Instantiates buffer with data (`numbers`).
-        // Usage allowing the buffer to be:
-        //   A storage buffer (can be bound within a bind group and thus available to a shader).
-        //   The destination of a copy.
-        //   The source of a copy.
-        let storage_buffer = gpu
-            .device
-            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
-                label: Some("Storage Buffer"),
-                contents: bytemuck::cast_slice(&numbers.input_output),
-                usage: wgpu::BufferUsages::STORAGE
-                    | wgpu::BufferUsages::COPY_DST
-                    | wgpu::BufferUsages::COPY_SRC,
-            });
+    /// Replaces the contents of `binding` (0 to 3) with `data` of any element type, e.g.
+    /// `u32` indices next to `f32` values. The GPU gets the bytes of `data`, padded with
+    /// zeros to whole `u32`s; read it back typed with `GpuConsts::read_binding_as`.
+    pub fn set_binding<T: bytemuck::Pod>(&mut self, binding: usize, data: &[T]) {
+        let buffer = match binding {
+            0 => &mut self.input_output,
+            1 => &mut self.shared_memory,
+            2 => &mut self.global_memory,
+            3 => &mut self.output_vec,
+            _ => panic!("there are only bindings 0 to 3, not {}", binding),
+        };
 
-        // A bind group defines how buffers are accessed by shaders.
-        // It is to WebGPU what a descriptor set is to Vulkan.
-        // `binding` here refers to the `binding` of a buffer in the shader (`layout(set = 0, binding = 0) buffer`).
+        // Byte-wise, so element types of any size and alignment work
+        let bytes: &[u8] = bytemuck::cast_slice(data);
+        buffer.clear();
+        buffer.extend(bytes.chunks(4).map(|chunk| {
+            let mut word = [0; 4];
+            word[..chunk.len()].copy_from_slice(chunk);
+            u32::from_ne_bytes(word)
+        }));
+        if binding == 0 {
+            self.generated_len = None;
+        }
+    }
 
-        // A pipeline specifies the operation of a shader
+    // Number of elements of binding 0 on the GPU
+    fn output_len(&self) -> usize {
+        self.generated_len.unwrap_or(self.input_output.len())
+    }
 
-        // Instantiates the pipeline.
-        let compute_pipeline =
-            gpu.device
-                .create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
-                    label: None,
-                    layout: None,
-                    module: &gpu.cs_module,
-                    entry_point: func_name,
-                });
+    /// Host copy of binding 0, updated by `GpuConsts::run_in_place`.
+    pub fn input_output(&self) -> &[u32] {
+        &self.input_output
+    }
+}
 
-        // Instantiates the bind group, once again specifying the binding of buffers.
-        let bind_group_layout = compute_pipeline.get_bind_group_layout(0);
+/// How many workgroups a dispatch launches.
+#[derive(Clone, Copy)]
+pub enum Dispatch<'a> {
+    /// Fixed `(x, y, z)` workgroup counts known on the host.
+    Workgroups(u32, u32, u32),
+    /// `[x, y, z]` counts read from a GPU buffer at the given byte offset.
+    Indirect(&'a Buffer, BufferAddress),
+}
 
-        let mut new_binding_entries: Vec<BindGroupEntry> = vec![wgpu::BindGroupEntry {
-            binding: 0,
-            resource: storage_buffer.as_entire_binding(),
-        }];
+/// Workgroup grid of `GpuConsts::plan_dispatch`, the fewest dimensions that fit the limits.
+/// The grid can be slightly larger than asked for, so the kernel has to decode its linear
+/// workgroup index as `id.x + id.y * x + id.z * x * y` and skip the ones past the count.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DispatchPlan {
+    /// Workgroup counts along x, y and z.
+    pub workgroups: (u32, u32, u32),
+    /// 1, 2 or 3, the number of dimensions that are more than one workgroup long.
+    pub dimensions: u32,
+}
 
-        let storage_buffer2 = gpu
-            .device
-            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
-                label: Some("Shared Memory Buffer"),
-                contents: bytemuck::cast_slice(&numbers.shared_memory),
-                usage: wgpu::BufferUsages::STORAGE,
-            });
+impl DispatchPlan {
+    pub fn dispatch(&self) -> Dispatch<'static> {
+        let (x, y, z) = self.workgroups;
+        Dispatch::Workgroups(x, y, z)
+    }
 
-        let storage_buffer3 = gpu
-            .device
-            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
-                label: Some("Global Memory Buffer"),
-                contents: bytemuck::cast_slice(&numbers.global_memory),
-                usage: wgpu::BufferUsages::STORAGE,
-            });
+    /// The linear index the kernel computes from `@builtin(workgroup_id)`.
+    pub fn workgroup_index(&self, id: (u32, u32, u32)) -> u64 {
+        let (x, y, _) = self.workgroups;
+        id.0 as u64 + id.1 as u64 * x as u64 + id.2 as u64 * x as u64 * y as u64
+    }
+}
 
-        let storage_buffer4 = gpu
-            .device
-            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
-                label: Some("Global Memory Buffer"),
-                contents: bytemuck::cast_slice(&numbers.output_vec),
-                usage: wgpu::BufferUsages::STORAGE,
-            });
+/// One kernel launch of a `BufCoder`; all stages share the same buffers.
+#[derive(Clone, Copy)]
+pub struct Stage<'a> {
+    pub func_name: &'a str,
+    pub binding_number: u32,
+    pub dispatch: Dispatch<'a>,
+    /// Module `func_name` is looked up in, `GpuConsts`'s own when `None`.
+    pub module: Option<&'a ShaderModule>,
+    /// Bindings zeroed on the GPU right before this stage, whatever their host data was.
+    pub clear_bindings: &'a [usize],
+    /// Uniform bound at `@group(1) @binding(0)`, e.g. the grid dimensions of `dispatch_2d`.
+    /// When `None`, entry points that declare it, of the main shader or of a module from
+    /// `GpuConsts::create_module`, get the element counts of bindings 0 to 3 there, see
+    /// `length_func.wgsl`.
+    pub params: Option<[u32; 4]>,
+    /// Already compiled pipeline for `func_name`, which is then not compiled again.
+    pub kernel: Option<&'a Kernel>,
+}
 
-        if binding_number > 1 {
-            new_binding_entries.push(wgpu::BindGroupEntry {
-                binding: 1,
-                resource: storage_buffer2.as_entire_binding(),
-            });
+/// An entry point compiled once by `GpuConsts::compile_kernel`, then dispatched any number
+/// of times with `Stage::from_kernel`.
+pub struct Kernel {
+    pub entry: String,
+    /// `@workgroup_size` of `entry`, the sizes it leaves out are 1.
+    pub workgroup_size: [u32; 3],
+    pub pipeline: ComputePipeline,
+    /// Layout of bind group 0 of `pipeline`.
+    pub bind_group_layout: BindGroupLayout,
+}
 
-            if binding_number > 2 {
-                new_binding_entries.push(wgpu::BindGroupEntry {
-                    binding: 2,
-                    resource: storage_buffer3.as_entire_binding(),
-                });
+impl Kernel {
+    /// Number of workgroups along x that gives every one of `len` elements its own invocation.
+    pub fn workgroups_for(&self, len: usize) -> u32 {
+        workgroup_count_for(len, self.workgroup_size[0])
+    }
+}
 
-                if binding_number > 3 {
-                    new_binding_entries.push(wgpu::BindGroupEntry {
-                        binding: 3,
-                        resource: storage_buffer4.as_entire_binding(),
-                    });
+// What reflection tells about one entry point of a shader.
+#[derive(Clone, Debug)]
+struct EntryReflection {
+    name: String,
+    // One past the highest binding of group 0 the entry point uses
+    binding_count: u32,
+    // Whether it uses the params uniform at `@group(1) @binding(0)`
+    uses_params: bool,
+    // Whether binding `i` of group 0 is `var<storage, read>`, for explicit layouts
+    read_only: Vec<bool>,
+}
+
+// A module from `GpuConsts::create_module`, reflected when it was compiled.
+struct CachedModule {
+    module: Arc<ShaderModule>,
+    entries: Vec<EntryReflection>,
+}
+
+// Reflects every entry point of WGSL `source`, none when `source` isn't valid.
+fn reflect_entries(source: &str) -> Vec<EntryReflection> {
+    let Ok(module) = naga::front::wgsl::parse_str(source) else {
+        return Vec::new();
+    };
+    let Ok(info) = naga::valid::Validator::new(
+        naga::valid::ValidationFlags::all(),
+        naga::valid::Capabilities::all(),
+    )
+    .validate(&module) else {
+        return Vec::new();
+    };
+
+    module
+        .entry_points
+        .iter()
+        .enumerate()
+        .map(|(index, entry_point)| {
+            let entry_info = info.get_entry_point(index);
+
+            // Globals the entry point never touches don't need a binding
+            let used: Vec<(&naga::ResourceBinding, naga::AddressSpace)> = module
+                .global_variables
+                .iter()
+                .filter(|(handle, _)| !entry_info[*handle].is_empty())
+                .filter_map(|(_, variable)| Some((variable.binding.as_ref()?, variable.space)))
+                .collect();
+
+            let binding_count = used
+                .iter()
+                .filter(|(binding, _)| binding.group == 0)
+                .map(|(binding, _)| binding.binding + 1)
+                .max()
+                .unwrap_or(0);
+            let mut read_only = vec![false; binding_count as usize];
+            for (binding, space) in &used {
+                if let (0, naga::AddressSpace::Storage { access }) = (binding.group, space) {
+                    read_only[binding.binding as usize] =
+                        !access.contains(naga::StorageAccess::STORE);
                 }
             }
+
+            EntryReflection {
+                name: entry_point.name.clone(),
+                binding_count,
+                uses_params: used
+                    .iter()
+                    .any(|(binding, _)| binding.group == 1 && binding.binding == 0),
+                read_only,
+            }
+        })
+        .collect()
+}
+
+/// The `binding_number` the entry point `entry` of WGSL `source` needs: one past the highest
+/// binding of group 0 it uses. `None` when `source` isn't valid or has no such entry point.
+pub fn reflect_binding_count(source: &str, entry: &str) -> Option<u32> {
+    reflect_entries(source)
+        .into_iter()
+        .find(|reflection| reflection.name == entry)
+        .map(|reflection| reflection.binding_count)
+}
+
+/// The `@workgroup_size` of the entry point `entry` in WGSL `source`, `None` when there is
+/// no such entry point or its sizes aren't integer literals.
+pub fn reflect_workgroup_size(source: &str, entry: &str) -> Option<[u32; 3]> {
+    const ATTRIBUTE: &str = "@workgroup_size(";
+
+    // The declaration of `entry`, and not of a function whose name only starts with it
+    let declaration = format!("fn {}", entry);
+    let fn_pos = source.match_indices(&declaration).find_map(|(pos, _)| {
+        let rest = &source[pos + declaration.len()..];
+        rest.trim_start().starts_with('(').then_some(pos)
+    })?;
+
+    // The closest attribute before it, unless that belongs to an earlier function
+    let args_start = source[..fn_pos].rfind(ATTRIBUTE)? + ATTRIBUTE.len();
+    let args_end = args_start + source[args_start..].find(')')?;
+    if source[args_end..fn_pos].contains("fn ") {
+        return None;
+    }
+
+    let mut size = [1; 3];
+    for (dim, arg) in source[args_start..args_end].split(',').enumerate().take(3) {
+        let arg = arg.trim().trim_end_matches(['u', 'i']);
+        if !arg.is_empty() {
+            size[dim] = arg.parse().ok()?;
         }
+    }
 
-        let bind_group = gpu.device.create_bind_group(&wgpu::BindGroupDescriptor {
-            label: None,
-            layout: &bind_group_layout,
-            entries: &new_binding_entries,
-        });
+    Some(size)
+}
 
-        // A command encoder executes one or many pipelines.
-        // It is to WebGPU what a command buffer is to Vulkan.
-        let mut encoder = gpu
-            .device
-            .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
-        {
-            let mut cpass =
-                encoder.begin_compute_pass(&wgpu::ComputePassDescriptor { label: None });
-            cpass.set_pipeline(&compute_pipeline);
-            cpass.set_bind_group(0, &bind_group, &[]);
-            cpass.insert_debug_marker("compute collatz iterations");
-            cpass.dispatch_workgroups(256, 1, 1); // Number of cells to run, the (x,y,z) size of item being processed
+/// `@workgroup_size` of the kernels `Stage::dispatch_2d` launches.
+pub const WORKGROUP_2D: (u32, u32) = (16, 16);
+
+/// `@workgroup_size` of the kernels `Stage::dispatch_3d` launches.
+pub const WORKGROUP_3D: (u32, u32, u32) = (8, 8, 4);
+
+impl<'a> Stage<'a> {
+    pub fn new(func_name: &'a str, binding_number: u32) -> Self {
+        Stage {
+            func_name,
+            binding_number,
+            dispatch: Dispatch::Workgroups(256, 1, 1),
+            module: None,
+            clear_bindings: &[],
+            params: None,
+            kernel: None,
         }
-        // Sets adds copy operation to command encoder.
-        // Will copy data from storage buffer on GPU to staging buffer on CPU.
-        encoder.copy_buffer_to_buffer(&storage_buffer, 0, &staging_buffer, 0, size);
+    }
 
-        // Submits command encoder for processing
-        gpu.queue.submit(Some(encoder.finish()));
+    /// A stage dispatching the compiled `kernel`.
+    pub fn from_kernel(kernel: &'a Kernel, binding_number: u32) -> Self {
+        Stage {
+            kernel: Some(kernel),
+            ..Stage::new(&kernel.entry, binding_number)
+        }
+    }
 
-        BufCoder { staging_buffer }
+    pub fn dispatch(mut self, dispatch: Dispatch<'a>) -> Self {
+        self.dispatch = dispatch;
+        self
+    }
+
+    pub fn module(mut self, module: &'a ShaderModule) -> Self {
+        self.module = Some(module);
+        self
+    }
+
+    pub fn params(mut self, params: [u32; 4]) -> Self {
+        self.params = Some(params);
+        self
+    }
+
+    /// Covers a `width x height` grid with `WORKGROUP_2D` workgroups and passes
+    /// `[width, height, 1, 0]` as the params, see `markCells2d_call` in `grid_func.wgsl`.
+    pub fn dispatch_2d(self, width: u32, height: u32) -> Self {
+        self.dispatch(Dispatch::Workgroups(
+            width.div_ceil(WORKGROUP_2D.0),
+            height.div_ceil(WORKGROUP_2D.1),
+            1,
+        ))
+        .params([width, height, 1, 0])
+    }
+
+    /// `dispatch_2d` for a `width x height x depth` grid of `WORKGROUP_3D` workgroups.
+    pub fn dispatch_3d(self, width: u32, height: u32, depth: u32) -> Self {
+        self.dispatch(Dispatch::Workgroups(
+            width.div_ceil(WORKGROUP_3D.0),
+            height.div_ceil(WORKGROUP_3D.1),
+            depth.div_ceil(WORKGROUP_3D.2),
+        ))
+        .params([width, height, depth, 0])
+    }
+
+    /// Zeroes `bindings` before the dispatch, e.g. the counters of an atomic kernel.
+    pub fn clear_before_dispatch(mut self, bindings: &'a [usize]) -> Self {
+        self.clear_bindings = bindings;
+        self
     }
 }
 
-pub struct GpuConsts {
-    _instance: Instance,
-    _adapter: Adapter,
-    device: Device,
-    queue: Queue,
-    _info: AdapterInfo,
-    cs_module: ShaderModule,
+// GPU timestamps written before the first and after the last stage of a `BufCoder`.
+struct Timestamps {
+    query_set: QuerySet,
+    resolve_buffer: Buffer,
+    readback_buffer: Buffer,
 }
 
-impl GpuConsts {
-    pub async fn initialaze(filename: &str) -> Result<GpuConsts, String> {
-        // Instantiates instance of WebGPU
-        let instance = wgpu::Instance::default();
+impl Timestamps {
+    const SIZE: BufferAddress = 2 * std::mem::size_of::<u64>() as BufferAddress;
 
-        // `request_adapter` instantiates the general connection to the GPU
-        let adapter = instance
-            .request_adapter(&wgpu::RequestAdapterOptions::default())
-            .await
-            .ok_or_else(|| "adapter error")?;
+    fn new(gpu: &GpuConsts) -> Self {
+        let device = &gpu.device;
+        let query_set = device.create_query_set(&wgpu::QuerySetDescriptor {
+            label: gpu.label("timestamps", Some("Timestamps")).as_deref(),
+            ty: wgpu::QueryType::Timestamp,
+            count: 2,
+        });
 
-        // `request_device` instantiates the feature specific connection to the GPU, defining some parameters,
-        //  `features` being the available features.
-        let (device, queue) = adapter
-            .request_device(
-                &wgpu::DeviceDescriptor {
-                    label: None,
-                    features: wgpu::Features::empty(),
-                    limits: wgpu::Limits::downlevel_defaults(),
-                },
-                None,
-            )
-            .await
-            .unwrap();
+        // Queries can only be resolved into a buffer that can't be mapped,
+        // so the result takes another copy to become readable.
+        let resolve_label = gpu.label("timestamps-resolve", Some("Timestamps Resolve Buffer"));
+        let readback_label = gpu.label("timestamps-readback", Some("Timestamps Readback Buffer"));
+        let resolve_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: resolve_label.as_deref(),
+            size: Timestamps::SIZE,
+            usage: wgpu::BufferUsages::QUERY_RESOLVE | wgpu::BufferUsages::COPY_SRC,
+            mapped_at_creation: false,
+        });
+        let readback_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: readback_label.as_deref(),
+            size: Timestamps::SIZE,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        trace_resource!(
+            "created",
+            "buffer",
+            resolve_buffer,
+            resolve_label.as_deref()
+        );
+        trace_resource!(
+            "created",
+            "buffer",
+            readback_buffer,
+            readback_label.as_deref()
+        );
 
-        let info = adapter.get_info();
+        Timestamps {
+            query_set,
+            resolve_buffer,
+            readback_buffer,
+        }
+    }
 
-        if info.vendor == 0x10005 {
-            return Err("info error".to_string());
+    fn resolve(&self, encoder: &mut CommandEncoder) {
+        encoder.resolve_query_set(&self.query_set, 0..2, &self.resolve_buffer, 0);
+        encoder.copy_buffer_to_buffer(
+            &self.resolve_buffer,
+            0,
+            &self.readback_buffer,
+            0,
+            Timestamps::SIZE,
+        );
+    }
+}
+
+/// Time spent in each phase of one `BufCoder` round trip.
+#[derive(Clone, Copy, Debug)]
+pub struct PhaseTimings {
+    /// Creating the GPU buffers and filling them with the host data.
+    pub upload: Duration,
+    /// Running all stages of the `BufCoder`.
+    pub compute: Duration,
+    /// Mapping the staging buffer and copying it to the host.
+    pub download: Duration,
+    /// Whether `compute` comes from GPU timestamps; otherwise it is the host-side wait for
+    /// the submission, which also includes the copy into the staging buffer.
+    pub gpu_timestamps: bool,
+}
+
+/// How the result of a `BufCoder` gets from the GPU to the host.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ReadbackPath {
+    /// Copied into a separate mappable staging buffer, needed on discrete GPUs.
+    Staging,
+    /// The storage buffer is mapped directly, on GPUs sharing memory with the host.
+    Direct,
+}
+
+/// Maps the storage buffer directly only on integrated (or software) adapters that allow
+/// mapping storage buffers; there the staging copy is just a copy within the same memory.
+pub fn select_readback_path(info: &AdapterInfo, features: wgpu::Features) -> ReadbackPath {
+    let shares_host_memory = matches!(
+        info.device_type,
+        wgpu::DeviceType::IntegratedGpu | wgpu::DeviceType::Cpu
+    );
+
+    if shares_host_memory && features.contains(wgpu::Features::MAPPABLE_PRIMARY_BUFFERS) {
+        ReadbackPath::Direct
+    } else {
+        ReadbackPath::Staging
+    }
+}
+
+/// Buffers of dropped `BufCoder`s, handed out again to new ones of the same size and usage.
+pub struct BufferPool {
+    free: Mutex<Vec<Buffer>>,
+    capacity: BufferAddress,
+}
+
+impl BufferPool {
+    /// A pool retaining at most `capacity` bytes of buffers.
+    pub fn new(capacity: BufferAddress) -> Self {
+        BufferPool {
+            free: Mutex::new(Vec::new()),
+            capacity,
         }
+    }
 
-        let cs_module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
-            label: None,
-            source: wgpu::ShaderSource::Wgsl(read_to_string(filename).unwrap().into()),
-        });
+    /// Bytes of buffers currently waiting to be reused.
+    pub fn retained_bytes(&self) -> BufferAddress {
+        self.free.lock().unwrap().iter().map(Buffer::size).sum()
+    }
 
-        Ok(GpuConsts {
-            _instance: instance,
-            _adapter: adapter,
-            device,
-            queue,
-            _info: info,
-            cs_module,
-        })
+    /// Number of buffers currently waiting to be reused.
+    pub fn len(&self) -> usize {
+        self.free.lock().unwrap().len()
     }
 
-    pub async fn run(&self, bufcoder: &BufCoder) -> Option<Vec<u32>> {
-        // Note that we're not calling `.await` here.
-        let buffer_slice = bufcoder.staging_buffer.slice(..);
-        // Sets the buffer up for mapping, sending over the result of the mapping back to us when it is finished.
-        let (sender, receiver) = futures_intrusive::channel::shared::oneshot_channel();
-        buffer_slice.map_async(wgpu::MapMode::Read, move |v| sender.send(v).unwrap());
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
 
-        // Poll the device in a blocking manner so that our future resolves.
-        // In an actual application, `device.poll(...)` should
-        // be called in an event loop or on another thread.
-        self.device.poll(wgpu::Maintain::Wait);
+    fn take(&self, size: BufferAddress, usage: BufferUsages) -> Option<Buffer> {
+        let mut free = self.free.lock().unwrap();
+        let index = free
+            .iter()
+            .position(|buffer| buffer.size() == size && buffer.usage() == usage)?;
 
-        // Awaits until `buffer_future` can be read from
-        if let Some(Ok(())) = receiver.receive().await {
-            // Gets contents of buffer
-            let data = buffer_slice.get_mapped_range();
-            // Since contents are got in bytes, this converts these bytes back to u32
-            let result = bytemuck::cast_slice(&data).to_vec();
+        let buffer = free.swap_remove(index);
+        trace_resource!("reused", "buffer", buffer);
+        Some(buffer)
+    }
 
-            // With the current interface, we have to make sure all mapped views are
-            // dropped before we unmap the buffer.
-            drop(data);
-            bufcoder.staging_buffer.unmap(); // Unmaps buffer from memory
-                                             // If you are familiar with C++ these 2 lines can be thought of similarly to:
-                                             //   delete myPointer;
-                                             //   myPointer = NULL;
-                                             // It effectively frees the memory
-
-            // Returns data from buffer
-            Some(result)
+    // Drops `buffer` instead when keeping it would exceed the capacity
+    fn put(&self, buffer: Buffer) {
+        let mut free = self.free.lock().unwrap();
+        let retained: BufferAddress = free.iter().map(Buffer::size).sum();
+        if retained + buffer.size() <= self.capacity {
+            trace_resource!("pooled", "buffer", buffer);
+            free.push(buffer);
         } else {
-            panic!("failed to run compute on gpu!")
+            trace_resource!("dropped", "buffer", buffer);
         }
     }
 }
 
-pub fn add_two_vec(a: &[u32], b: &[u32], cap: usize) -> Vec<u32> {
-    let mut res = Vec::with_capacity(cap);
-
-    for i in 0..cap {
-        res.push(a[i] + b[i]);
-    }
+pub struct BufCoder {
+    staging_buffer: Option<Buffer>,
+    storage_buffers: Vec<Buffer>,
+    submission_index: SubmissionIndex,
+    upload_time: Duration,
+    timestamps: Option<Timestamps>,
+    // Where the buffers go back to when the `BufCoder` is dropped
+    pool: Arc<BufferPool>,
+    // Whether a result was copied to a staging buffer that nothing has read yet
+    unread: std::sync::atomic::AtomicBool,
+}
 
-    return res;
+// The buffers of a `BufCoder` whose commands aren't submitted yet.
+struct Recorded {
+    staging_buffer: Option<Buffer>,
+    storage_buffers: Vec<Buffer>,
+    upload_time: Duration,
+    timestamps: Option<Timestamps>,
 }
 
-pub fn batch_add_two_vec(a: &[u32], b: &[u32], cap: usize, batch: u32) {
-    for _ in 0..batch {
-        add_two_vec(a, b, cap);
+impl Recorded {
+    fn into_bufcoder(self, gpu: &GpuConsts, submission_index: SubmissionIndex) -> BufCoder {
+        let unread = self.staging_buffer.is_some();
+
+        BufCoder {
+            staging_buffer: self.staging_buffer,
+            storage_buffers: self.storage_buffers,
+            submission_index,
+            upload_time: self.upload_time,
+            timestamps: self.timestamps,
+            pool: gpu.pool.clone(),
+            unread: std::sync::atomic::AtomicBool::new(unread),
+        }
     }
 }
 
-pub fn sum_vec(a: &[u32], cap: usize) -> u32 {
-    let mut res = 0;
+impl Drop for BufCoder {
+    fn drop(&mut self) {
+        // A result copied back but never read is usually a forgotten `run`
+        #[cfg(feature = "trace-resources")]
+        if self.unread.load(std::sync::atomic::Ordering::Relaxed) {
+            tracing::warn!(
+                target: "wgsl_example::resources",
+                "BufCoder dropped without its result being read, see `BufCoder::discard`"
+            );
+        }
 
-    for i in 0..cap {
-        res += a[i];
+        for buffer in self
+            .storage_buffers
+            .drain(..)
+            .chain(self.staging_buffer.take())
+        {
+            self.pool.put(buffer);
+        }
     }
-
-    return res;
 }
 
-pub fn batch_sum_vec(a: &[u32], cap: usize, batch: u32) {
-    for _ in 0..batch {
-        sum_vec(a, cap);
+impl BufCoder {
+    pub fn initialize(
+        gpu: &GpuConsts,
+        numbers: &mut Bindings,
+        func_name: &str,
+        binding_number: u32,
+    ) -> BufCoder {
+        BufCoder::initialize_stages(gpu, numbers, &[Stage::new(func_name, binding_number)])
     }
-}
 
-pub fn optimized_sum_vec(arr: &[u32], start: usize, end: usize) -> u32 {
-    if end == start {
-        return arr[end];
+    /// Same as `initialize`, but the workgroup counts come from `indirect` at `offset`,
+    /// e.g. the `storage_buffer` of a previous `BufCoder` whose kernel wrote them.
+    pub fn initialize_indirect(
+        gpu: &GpuConsts,
+        numbers: &mut Bindings,
+        func_name: &str,
+        binding_number: u32,
+        indirect: &Buffer,
+        offset: BufferAddress,
+    ) -> BufCoder {
+        BufCoder::initialize_stages(
+            gpu,
+            numbers,
+            &[
+                Stage::new(func_name, binding_number)
+                    .dispatch(Dispatch::Indirect(indirect, offset)),
+            ],
+        )
     }
-    if end - start == 1 {
-        return arr[start] + arr[end];
-    } else {
-        return optimized_sum_vec(arr, start, (end - start) / 2 + start)
-            + optimized_sum_vec(arr, (end - start) / 2 + start + 1, end);
+
+    /// `initialize` that fails instead of letting the kernel see a truncated length.
+    pub fn try_initialize(
+        gpu: &GpuConsts,
+        numbers: &mut Bindings,
+        func_name: &str,
+        binding_number: u32,
+    ) -> Result<BufCoder, GpuError> {
+        BufCoder::try_initialize_stages(gpu, numbers, &[Stage::new(func_name, binding_number)])
     }
-}
 
-pub fn batch_optimized_sum_vec(arr: &[u32], start: usize, end: usize, batch: u32) {
-    for _ in 0..batch {
-        optimized_sum_vec(arr, start, end);
+    /// `initialize_stages` that fails instead of letting the kernel see a truncated length.
+    pub fn try_initialize_stages(
+        gpu: &GpuConsts,
+        numbers: &mut Bindings,
+        stages: &[Stage],
+    ) -> Result<BufCoder, GpuError> {
+        for binding in [
+            &numbers.input_output,
+            &numbers.shared_memory,
+            &numbers.global_memory,
+            &numbers.output_vec,
+        ] {
+            check_len(binding.len())?;
+        }
+        check_len(numbers.output_len())?;
+
+        // Only stages of the main shader can be checked, the source of other modules is unknown
+        for stage in stages.iter().filter(|stage| stage.module.is_none()) {
+            if let Some(expected) = gpu.binding_count(stage.func_name) {
+                if expected != stage.binding_number {
+                    return Err(GpuError::BindingCountMismatch {
+                        entry: stage.func_name.to_string(),
+                        expected,
+                        given: stage.binding_number,
+                    });
+                }
+            }
+        }
+
+        Ok(BufCoder::initialize_stages(gpu, numbers, stages))
     }
-}
+
+    /// `try_initialize_stages` that submits nothing and fails with `Cancelled` when `token`
+    /// is already cancelled.
+    pub fn initialize_cancellable(
+        gpu: &GpuConsts,
+        numbers: &mut Bindings,
+        stages: &[Stage],
+        token: &CancelToken,
+    ) -> Result<BufCoder, GpuError> {
+        if token.is_cancelled() {
+            return Err(GpuError::Cancelled);
+        }
+        BufCoder::try_initialize_stages(gpu, numbers, stages)
+    }
+
+    /// Records every stage, in order, into one command encoder and submits it once.
+    /// Each stage gets its own compute pass, so a stage sees all writes of the previous ones.
+    pub fn initialize_stages(
+        gpu: &GpuConsts,
+        numbers: &mut Bindings,
+        stages: &[Stage],
+    ) -> BufCoder {
+        BufCoder::record(gpu, numbers, stages, true)
+    }
+
+    /// `initialize_stages` without the staging copy of the whole output, for results read
+    /// with `GpuConsts::read_scalar` or `GpuConsts::read_binding`. `GpuConsts::run` can't
+    /// read it.
+    pub fn initialize_stages_without_readback(
+        gpu: &GpuConsts,
+        numbers: &mut Bindings,
+        stages: &[Stage],
+    ) -> BufCoder {
+        BufCoder::record(gpu, numbers, stages, false)
+    }
+
+    // Without `readback` the result can't be read, there is neither a staging buffer
+    // nor a mappable storage buffer.
+    fn record(
+        gpu: &GpuConsts,
+        numbers: &mut Bindings,
+        stages: &[Stage],
+        readback: bool,
+    ) -> BufCoder {
+        let (commands, recorded) = BufCoder::encode(gpu, numbers, stages, readback);
+
+        // Submits command encoder for processing
+        let submission_index = gpu.queue.submit(Some(commands));
+
+        recorded.into_bufcoder(gpu, submission_index)
+    }
+
+    // `record` without the submission, so several can be submitted together.
+    fn encode(
+        gpu: &GpuConsts,
+        numbers: &mut Bindings,
+        stages: &[Stage],
+        readback: bool,
+    ) -> (wgpu::CommandBuffer, Recorded) {
+        // Gets the size in bytes of the buffer.
+        let slice_size = numbers.output_len() * std::mem::size_of::<u32>();
+        let size = slice_size as wgpu::BufferAddress;
+
+        // Instantiates buffer without data, unless the storage buffer can be mapped itself.
+        // `usage` of buffer specifies how it can be used:
+        //   `BufferUsages::MAP_READ` allows it to be read (outside the shader).
+        //   `BufferUsages::COPY_DST` allows it to be the destination of the copy.
+        let staging_buffer = (readback && gpu.readback_path == ReadbackPath::Staging).then(|| {
+            let usage = wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST;
+            gpu.pool.take(size, usage).unwrap_or_else(|| {
+                let label = gpu.label("staging", Some("Staging Buffer"));
+                let buffer = gpu.device.create_buffer(&wgpu::BufferDescriptor {
+                    label: label.as_deref(),
+                    size,
+                    usage,
+                    mapped_at_creation: false,
+                });
+                trace_resource!("created", "buffer", buffer, label.as_deref());
+                buffer
+            })
+        });
+        let direct_read = if readback && staging_buffer.is_none() {
+            wgpu::BufferUsages::MAP_READ
+        } else {
+            wgpu::BufferUsages::empty()
+        };
+
+        let upload_start = Instant::now();
+
+        // Instantiates buffer with data (`numbers`), reusing a pooled one when possible.
+        // Usage allowing the buffer to be:
+        //   A storage buffer (can be bound within a bind group and thus available to a shader).
+        //   The destination of a copy.
+        //   The source of a copy.
+        //   The source of indirect dispatch arguments for a following kernel.
+        //   Mapped for reading, when there is no staging buffer.
+        //   Generators upload nothing, unless seeded, the buffer is zeroed on the GPU instead.
+        let storage_usage = wgpu::BufferUsages::STORAGE
+            | wgpu::BufferUsages::COPY_DST
+            | wgpu::BufferUsages::COPY_SRC
+            | wgpu::BufferUsages::INDIRECT
+            | direct_read;
+        let storage_buffer = match numbers.generated_len {
+            Some(_) => gpu.pool.take(size, storage_usage).unwrap_or_else(|| {
+                let label = gpu.label("storage-0", Some("Storage Buffer"));
+                let buffer = gpu.device.create_buffer(&wgpu::BufferDescriptor {
+                    label: label.as_deref(),
+                    size,
+                    usage: storage_usage,
+                    mapped_at_creation: false,
+                });
+                trace_resource!("created", "buffer", buffer, label.as_deref());
+                buffer
+            }),
+            None => BufCoder::create_filled(
+                gpu,
+                gpu.label("storage-0", Some("Storage Buffer")).as_deref(),
+                &numbers.input_output,
+                storage_usage,
+            ),
+        };
+
+        let storage_buffer2 = BufCoder::create_filled(
+            gpu,
+            gpu.label("storage-1", Some("Shared Memory Buffer"))
+                .as_deref(),
+            &numbers.shared_memory,
+            wgpu::BufferUsages::STORAGE
+                | wgpu::BufferUsages::COPY_DST
+                | wgpu::BufferUsages::COPY_SRC,
+        );
+
+        let storage_buffer3 = BufCoder::create_filled(
+            gpu,
+            gpu.label("storage-2", Some("Global Memory Buffer"))
+                .as_deref(),
+            &numbers.global_memory,
+            wgpu::BufferUsages::STORAGE
+                | wgpu::BufferUsages::COPY_DST
+                | wgpu::BufferUsages::COPY_SRC,
+        );
+
+        let storage_buffer4 = BufCoder::create_filled(
+            gpu,
+            gpu.label("storage-3", Some("Global Memory Buffer"))
+                .as_deref(),
+            &numbers.output_vec,
+            wgpu::BufferUsages::STORAGE
+                | wgpu::BufferUsages::COPY_DST
+                | wgpu::BufferUsages::COPY_SRC,
+        );
+
+        // Every binding can be copied from, so any of them can be read back with `read_binding`.
+        let storage_buffers = vec![
+            storage_buffer,
+            storage_buffer2,
+            storage_buffer3,
+            storage_buffer4,
+        ];
+
+        let upload_time = upload_start.elapsed();
+
+        // A command encoder executes one or many pipelines.
+        // It is to WebGPU what a command buffer is to Vulkan.
+        let mut encoder = gpu
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+
+        // Measures the stages on the GPU itself when the device supports it
+        let timestamps = gpu
+            .device
+            .features()
+            .contains(wgpu::Features::TIMESTAMP_QUERY)
+            .then(|| Timestamps::new(gpu));
+        if let Some(timestamps) = &timestamps {
+            encoder.write_timestamp(&timestamps.query_set, 0);
+        }
+
+        // A pooled buffer still holds what its previous user left there. Queue writes land
+        // before the commands of the next submission, so the seed is there for the first stage.
+        match numbers.generated_len {
+            Some(_) if numbers.generated_seed == 0 => {
+                encoder.clear_buffer(&storage_buffers[0], 0, None);
+            }
+            Some(len) => gpu.queue.write_buffer(
+                &storage_buffers[0],
+                0,
+                bytemuck::cast_slice(&vec![numbers.generated_seed; len]),
+            ),
+            None => {}
+        }
+
+        for stage in stages {
+            // Clears are recorded outside of compute passes
+            for &binding in stage.clear_bindings {
+                encoder.clear_buffer(&storage_buffers[binding], 0, None);
+            }
+
+            // A pipeline specifies the operation of a shader
+
+            // Instantiates the pipeline, unless the stage brings a compiled one.
+            let pipeline_label = gpu.label(&format!("pipeline-{}", stage.func_name), None);
+            let compiled = stage.kernel.is_none().then(|| {
+                let pipeline =
+                    gpu.device
+                        .create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                            label: pipeline_label.as_deref(),
+                            layout: None,
+                            module: stage.module.unwrap_or(&gpu.cs_module),
+                            entry_point: stage.func_name,
+                        });
+                trace_resource!("created", "pipeline", pipeline, pipeline_label.as_deref());
+                pipeline
+            });
+            let compute_pipeline = match stage.kernel {
+                Some(kernel) => &kernel.pipeline,
+                None => compiled.as_ref().unwrap(),
+            };
+
+            // A bind group defines how buffers are accessed by shaders.
+            // It is to WebGPU what a descriptor set is to Vulkan.
+            // `binding` here refers to the `binding` of a buffer in the shader (`layout(set = 0, binding = 0) buffer`).
+
+            // Instantiates the bind group, once again specifying the binding of buffers.
+            let queried_layout;
+            let bind_group_layout = match stage.kernel {
+                Some(kernel) => &kernel.bind_group_layout,
+                None => {
+                    queried_layout = compute_pipeline.get_bind_group_layout(0);
+                    &queried_layout
+                }
+            };
+
+            let new_binding_entries: Vec<BindGroupEntry> = storage_buffers
+                .iter()
+                .take(stage.binding_number as usize)
+                .enumerate()
+                .map(|(binding, buffer)| wgpu::BindGroupEntry {
+                    binding: binding as u32,
+                    resource: buffer.as_entire_binding(),
+                })
+                .collect();
+
+            let bind_group_label = gpu.label(&format!("bind-group-{}", stage.func_name), None);
+            let bind_group = gpu.device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: bind_group_label.as_deref(),
+                layout: bind_group_layout,
+                entries: &new_binding_entries,
+            });
+            trace_resource!(
+                "created",
+                "bind group",
+                bind_group,
+                bind_group_label.as_deref()
+            );
+
+            // Small per-stage values go into their own uniform, in bind group 1
+            let lengths = || {
+                gpu.uses_params(stage.module, stage.func_name).then(|| {
+                    [
+                        numbers.output_len() as u32,
+                        numbers.shared_memory.len() as u32,
+                        numbers.global_memory.len() as u32,
+                        numbers.output_vec.len() as u32,
+                    ]
+                })
+            };
+            let params = stage.params.or_else(lengths).map(|params| {
+                let buffer_label = gpu.label("params", Some("Params Buffer"));
+                let buffer = gpu
+                    .device
+                    .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                        label: buffer_label.as_deref(),
+                        contents: bytemuck::cast_slice(&params),
+                        usage: wgpu::BufferUsages::UNIFORM,
+                    });
+                trace_resource!("created", "buffer", buffer, buffer_label.as_deref());
+                let bind_group_label =
+                    gpu.label(&format!("params-bind-group-{}", stage.func_name), None);
+                let bind_group = gpu.device.create_bind_group(&wgpu::BindGroupDescriptor {
+                    label: bind_group_label.as_deref(),
+                    layout: &compute_pipeline.get_bind_group_layout(1),
+                    entries: &[wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: buffer.as_entire_binding(),
+                    }],
+                });
+                trace_resource!(
+                    "created",
+                    "bind group",
+                    bind_group,
+                    bind_group_label.as_deref()
+                );
+                (buffer, bind_group)
+            });
+
+            let mut cpass =
+                encoder.begin_compute_pass(&wgpu::ComputePassDescriptor { label: None });
+            cpass.set_pipeline(compute_pipeline);
+            cpass.set_bind_group(0, &bind_group, &[]);
+            if let Some((_, params_bind_group)) = &params {
+                cpass.set_bind_group(1, params_bind_group, &[]);
+            }
+            cpass.insert_debug_marker(stage.func_name);
+            match stage.dispatch {
+                // Number of cells to run, the (x,y,z) size of item being processed
+                Dispatch::Workgroups(x, y, z) => cpass.dispatch_workgroups(x, y, z),
+                // The same (x,y,z), but written to `indirect` by an earlier kernel
+                Dispatch::Indirect(indirect, offset) => {
+                    cpass.dispatch_workgroups_indirect(indirect, offset)
+                }
+            }
+
+            // The encoder keeps what the pass recorded alive, the handles can go
+            drop(cpass);
+            trace_resource!("dropped", "bind group", bind_group);
+            if let Some((buffer, bind_group)) = &params {
+                trace_resource!("dropped", "bind group", bind_group);
+                trace_resource!("dropped", "buffer", buffer);
+            }
+            if let Some(compiled) = &compiled {
+                trace_resource!("dropped", "pipeline", compiled);
+            }
+        }
+        if let Some(timestamps) = &timestamps {
+            encoder.write_timestamp(&timestamps.query_set, 1);
+            timestamps.resolve(&mut encoder);
+        }
+
+        // Sets adds copy operation to command encoder.
+        // Will copy data from storage buffer on GPU to staging buffer on CPU.
+        if let Some(staging_buffer) = &staging_buffer {
+            encoder.copy_buffer_to_buffer(&storage_buffers[0], 0, staging_buffer, 0, size);
+        }
+
+        (
+            encoder.finish(),
+            Recorded {
+                staging_buffer,
+                storage_buffers,
+                upload_time,
+                timestamps,
+            },
+        )
+    }
+
+    // A buffer holding `contents`, from the pool of `gpu` when it has a matching one.
+    fn create_filled(
+        gpu: &GpuConsts,
+        label: Option<&str>,
+        contents: &[u32],
+        usage: BufferUsages,
+    ) -> Buffer {
+        let size = std::mem::size_of_val(contents) as BufferAddress;
+        match gpu.pool.take(size, usage) {
+            Some(buffer) => {
+                // The write is ordered before any later submission that uses the buffer
+                gpu.queue
+                    .write_buffer(&buffer, 0, bytemuck::cast_slice(contents));
+                buffer
+            }
+            None => {
+                let buffer = gpu
+                    .device
+                    .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                        label,
+                        contents: bytemuck::cast_slice(contents),
+                        usage,
+                    });
+                trace_resource!("created", "buffer", buffer, label);
+                buffer
+            }
+        }
+    }
+
+    /// The GPU buffer bound at binding 0, which also holds the kernel output.
+    pub fn storage_buffer(&self) -> &Buffer {
+        self.mark_read();
+        &self.storage_buffers[0]
+    }
+
+    /// Drops the `BufCoder` without the warning of the `trace-resources` feature about a
+    /// result that was never read, for work run only for its timing or side effects.
+    pub fn discard(self) {
+        self.mark_read();
+    }
+
+    // Handing out a buffer the result is in counts as reading it.
+    fn mark_read(&self) {
+        self.unread
+            .store(false, std::sync::atomic::Ordering::Relaxed);
+    }
+
+    // The buffer `GpuConsts::run` maps, see `ReadbackPath`.
+    fn readback_buffer(&self) -> &Buffer {
+        self.mark_read();
+        self.staging_buffer
+            .as_ref()
+            .unwrap_or(&self.storage_buffers[0])
+    }
+
+    /// Identifies the submission of this `BufCoder`'s work, see `GpuConsts::wait_for`.
+    pub fn submission_index(&self) -> &SubmissionIndex {
+        &self.submission_index
+    }
+
+    /// The GPU buffer bound at `binding`.
+    pub fn binding_buffer(&self, binding: usize) -> &Buffer {
+        self.mark_read();
+        &self.storage_buffers[binding]
+    }
+}
+
+/// A dispatch whose buffers, pipeline and bind group are created once by
+/// `GpuConsts::prepare_dispatch`, for tight loops over inputs of the same size.
+/// wgpu can't submit a `CommandBuffer` twice, so every `run` still records one, but only
+/// the dispatch and the copy into the staging buffer.
+pub struct PreparedDispatch {
+    pipeline: ComputePipeline,
+    bind_group: wgpu::BindGroup,
+    storage_buffers: Vec<Buffer>,
+    staging_buffer: Buffer,
+    workgroups: (u32, u32, u32),
+}
+
+impl PreparedDispatch {
+    /// Uploads `input` into binding 0, dispatches and reads binding 0 back. The other
+    /// bindings keep what the previous run left there. `input` has the prepared length.
+    pub fn run(&self, gpu: &GpuConsts, input: &[u32]) -> Vec<u32> {
+        let size = self.staging_buffer.size();
+        assert_eq!(
+            std::mem::size_of_val(input) as BufferAddress,
+            size,
+            "input has to have the length binding 0 was prepared with"
+        );
+
+        gpu.queue
+            .write_buffer(&self.storage_buffers[0], 0, bytemuck::cast_slice(input));
+
+        let mut encoder = gpu
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+        {
+            let mut cpass =
+                encoder.begin_compute_pass(&wgpu::ComputePassDescriptor { label: None });
+            cpass.set_pipeline(&self.pipeline);
+            cpass.set_bind_group(0, &self.bind_group, &[]);
+            let (x, y, z) = self.workgroups;
+            cpass.dispatch_workgroups(x, y, z);
+        }
+        encoder.copy_buffer_to_buffer(&self.storage_buffers[0], 0, &self.staging_buffer, 0, size);
+        gpu.queue.submit(Some(encoder.finish()));
+
+        pollster::block_on(gpu.map_staging(&self.staging_buffer, |data| {
+            bytemuck::cast_slice(data).to_vec()
+        }))
+    }
+}
+
+/// `slots` staging buffers of `slot_size` bytes each that consecutive dispatches read back
+/// through in turn, so the result of one can be mapped while the next one computes.
+/// Keeps `slots * slot_size` bytes of host-visible memory; a slot is overwritten `slots`
+/// enqueues later, so at most `slots` results may wait to be read at a time.
+pub struct ReadbackRing {
+    slots: Vec<(Buffer, Option<SubmissionIndex>)>,
+    slot_size: BufferAddress,
+    next: usize,
+}
+
+impl ReadbackRing {
+    pub fn new(gpu: &GpuConsts, slots: usize, slot_size: BufferAddress) -> Self {
+        assert!(slots > 0, "a readback ring needs at least one slot");
+
+        let slots = (0..slots)
+            .map(|_| {
+                let buffer = gpu.device.create_buffer(&wgpu::BufferDescriptor {
+                    label: gpu
+                        .label("readback-ring", Some("Readback Ring Buffer"))
+                        .as_deref(),
+                    size: slot_size,
+                    usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+                    mapped_at_creation: false,
+                });
+                (buffer, None)
+            })
+            .collect();
+
+        ReadbackRing {
+            slots,
+            slot_size,
+            next: 0,
+        }
+    }
+
+    /// Copies the output of `bufcoder` into the next slot and returns that slot for `read`.
+    /// `None` when the output doesn't fit into a slot.
+    pub fn enqueue(&mut self, gpu: &GpuConsts, bufcoder: &BufCoder) -> Option<usize> {
+        let size = bufcoder.storage_buffer().size();
+        if size > self.slot_size {
+            return None;
+        }
+
+        let slot = self.next;
+        self.next = (self.next + 1) % self.slots.len();
+
+        let mut encoder = gpu
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+        encoder.copy_buffer_to_buffer(bufcoder.storage_buffer(), 0, &self.slots[slot].0, 0, size);
+        self.slots[slot].1 = Some(gpu.queue.submit(Some(encoder.finish())));
+
+        Some(slot)
+    }
+
+    /// The first `len` values copied into `slot` by `enqueue`. Only waits for that copy,
+    /// not for work submitted after it.
+    pub async fn read(&self, gpu: &GpuConsts, slot: usize, len: usize) -> Vec<u32> {
+        let (buffer, submission_index) = &self.slots[slot];
+        let size = (len * std::mem::size_of::<u32>()) as BufferAddress;
+        if size == 0 {
+            return Vec::new();
+        }
+
+        let buffer_slice = buffer.slice(..size);
+        let (sender, receiver) = futures_intrusive::channel::shared::oneshot_channel();
+        buffer_slice.map_async(wgpu::MapMode::Read, move |v| sender.send(v).unwrap());
+        match submission_index {
+            Some(index) => gpu
+                .device
+                .poll(wgpu::Maintain::WaitForSubmissionIndex(index.clone())),
+            None => gpu.device.poll(wgpu::Maintain::Wait),
+        };
+
+        if let Some(Ok(())) = receiver.receive().await {
+            let result = bytemuck::cast_slice(&buffer_slice.get_mapped_range()).to_vec();
+            buffer.unmap();
+            result
+        } else {
+            panic!("failed to run compute on gpu!")
+        }
+    }
+}
+
+// See `GpuConsts::supports_compute`.
+fn adapter_supports_compute(adapter: &Adapter) -> bool {
+    let capabilities = adapter.get_downlevel_capabilities();
+    let limits = adapter.limits();
+
+    capabilities
+        .flags
+        .contains(wgpu::DownlevelFlags::COMPUTE_SHADERS)
+        && limits.max_compute_workgroups_per_dimension > 0
+        && limits.max_compute_invocations_per_workgroup > 0
+}
+
+// See `GpuConsts::device_memory_hint`.
+fn adapter_memory_hint(adapter: &Adapter) -> Option<u64> {
+    let max_buffer_size = adapter.limits().max_buffer_size;
+    (max_buffer_size != u64::MAX).then_some(max_buffer_size)
+}
+
+/// How often, and how patiently, a failed adapter request is repeated.
+#[derive(Clone, Copy, Debug)]
+pub struct RetryPolicy {
+    /// Attempts after the first one.
+    pub retries: u32,
+    /// Pause before the first retry, doubled before every further one.
+    pub backoff: Duration,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        RetryPolicy {
+            retries: 2,
+            backoff: Duration::from_millis(50),
+        }
+    }
+}
+
+impl RetryPolicy {
+    /// Calls `attempt` until it returns `Some`, at most `retries + 1` times.
+    pub async fn run<T, F: std::future::Future<Output = Option<T>>>(
+        &self,
+        attempt: impl FnMut() -> F,
+    ) -> Option<T> {
+        self.run_with_sleep(attempt, backoff_sleep).await
+    }
+
+    /// `run` waiting out every backoff with `sleep`, e.g. the timer of an async runtime,
+    /// or one that only records the pauses in tests.
+    pub async fn run_with_sleep<T, F, S>(
+        &self,
+        mut attempt: impl FnMut() -> F,
+        mut sleep: impl FnMut(Duration) -> S,
+    ) -> Option<T>
+    where
+        F: std::future::Future<Output = Option<T>>,
+        S: std::future::Future<Output = ()>,
+    {
+        let mut backoff = self.backoff;
+        for _ in 0..self.retries {
+            if let Some(result) = attempt().await {
+                return Some(result);
+            }
+            sleep(backoff).await;
+            backoff *= 2;
+        }
+
+        attempt().await
+    }
+}
+
+// Completes after `duration` on any executor without blocking it: a helper thread sleeps
+// and then sends the wakeup.
+async fn backoff_sleep(duration: Duration) {
+    let (sender, receiver) = futures_intrusive::channel::shared::oneshot_channel();
+    std::thread::spawn(move || {
+        std::thread::sleep(duration);
+        sender.send(()).ok();
+    });
+
+    receiver.receive().await;
+}
+
+/// Which queue compute work should be submitted to.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum QueuePreference {
+    /// The device's only queue.
+    #[default]
+    Default,
+    /// A dedicated or high-priority compute queue, where the backend offers one. wgpu 0.17
+    /// offers none, so for now this runs on the default queue with `QueueInfo::applied` false.
+    HighPriority,
+}
+
+/// The queue a `GpuConsts` ended up with.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct QueueInfo {
+    pub requested: QueuePreference,
+    /// Whether `requested` could be honoured, the default queue is used otherwise.
+    pub applied: bool,
+}
+
+/// Everything known about the adapter and the device of a `GpuConsts`, to paste into
+/// issues; its `Display` is one fact per line.
+#[derive(Clone, Debug)]
+pub struct CapabilityReport {
+    pub info: AdapterInfo,
+    /// Features the device was created with.
+    pub features: wgpu::Features,
+    /// Limits the device was created with.
+    pub limits: wgpu::Limits,
+    pub queue: QueueInfo,
+    pub readback_path: ReadbackPath,
+}
+
+impl std::fmt::Display for CapabilityReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let info = &self.info;
+        writeln!(f, "adapter: {}", info.name)?;
+        writeln!(f, "backend: {:?}", info.backend)?;
+        writeln!(f, "device type: {:?}", info.device_type)?;
+        writeln!(
+            f,
+            "vendor id: {:#06x}, device id: {:#06x}",
+            info.vendor, info.device
+        )?;
+        writeln!(f, "driver: {} {}", info.driver, info.driver_info)?;
+        writeln!(f, "features: {:?}", self.features)?;
+        writeln!(f, "queue: {:?}", self.queue)?;
+        writeln!(f, "readback path: {:?}", self.readback_path)?;
+        write!(f, "limits: {:#?}", self.limits)
+    }
+}
+
+/// Settings for `GpuConsts::initialaze_with`.
+#[derive(Clone, Debug)]
+pub struct GpuOptions {
+    /// Backends searched for an adapter.
+    pub backends: wgpu::Backends,
+    /// Bytes of buffers the `BufferPool` keeps for reuse.
+    pub pool_capacity: BufferAddress,
+    /// Adapter requests can fail spuriously right after the driver is loaded.
+    pub retry: RetryPolicy,
+    /// Falls back to the default queue when it can't be honoured, see `GpuConsts::queue_info`.
+    pub queue_preference: QueuePreference,
+    /// Instance shared by several contexts, then `backends` is the one it was created with.
+    /// A new instance is created for every context when `None`.
+    pub instance: Option<Arc<Instance>>,
+    /// Prefixed to the labels of the device and of every buffer, texture, shader, pipeline
+    /// and bind group the context creates, e.g. `bench42-storage-0`, to tell contexts apart
+    /// in a GPU debugger capture.
+    /// Buffers reused from the `BufferPool` keep the label they were created with.
+    pub label_prefix: Option<String>,
+}
+
+impl Default for GpuOptions {
+    fn default() -> Self {
+        GpuOptions {
+            backends: wgpu::Backends::all(),
+            pool_capacity: 256 << 20,
+            retry: RetryPolicy::default(),
+            queue_preference: QueuePreference::Default,
+            instance: None,
+            label_prefix: None,
+        }
+    }
+}
+
+/// Why a `GpuConsts` couldn't be created.
+#[derive(Debug)]
+pub enum GpuError {
+    /// No adapter on any of `backends`, even though the software `fallback_attempted`.
+    NoAdapter {
+        backends: wgpu::Backends,
+        fallback_attempted: bool,
+    },
+    /// The adapter refused to create a device.
+    Device(wgpu::RequestDeviceError),
+    /// Only an adapter this demo doesn't run on was found.
+    UnsupportedAdapter(AdapterInfo),
+    /// The shader file couldn't be read.
+    Shader(String, std::io::Error),
+    /// A binding of `len` elements, more than `arrayLength` can report as `u32`.
+    InputTooLong { len: usize },
+    /// `requested` is more than the device allows for the `wgpu::Limits` field `limit`,
+    /// or than a kernel allows for its parameter `limit`, like the window of `gpu_window_sum`.
+    LimitExceeded {
+        limit: &'static str,
+        requested: u64,
+        max: u64,
+    },
+    /// The result couldn't be read back from the GPU.
+    Readback,
+    /// `entry` uses `expected` bindings of group 0, but was given `given`.
+    BindingCountMismatch {
+        entry: String,
+        expected: u32,
+        given: u32,
+    },
+    /// A result of `len` elements was asked for as an array of `shape`.
+    ShapeMismatch { shape: (usize, usize), len: usize },
+    /// A result of `bytes` bytes was read back as elements of `elem_size` bytes.
+    MisalignedReadback { bytes: usize, elem_size: usize },
+    /// The `CancelToken` of the work was cancelled.
+    Cancelled,
+    /// The output buffer has `actual_bytes`, the declared output length `expected_bytes`.
+    OutputLengthMismatch {
+        expected_bytes: u64,
+        actual_bytes: u64,
+    },
+    /// `count` elements `stride` apart reach past the `len` elements of the input.
+    StrideOutOfBounds {
+        stride: usize,
+        count: usize,
+        len: usize,
+    },
+}
+
+impl std::fmt::Display for GpuError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GpuError::NoAdapter {
+                backends,
+                fallback_attempted,
+            } => {
+                let names = [
+                    (wgpu::Backends::VULKAN, "vulkan"),
+                    (wgpu::Backends::METAL, "metal"),
+                    (wgpu::Backends::DX12, "dx12"),
+                    (wgpu::Backends::DX11, "dx11"),
+                    (wgpu::Backends::GL, "gl"),
+                    (wgpu::Backends::BROWSER_WEBGPU, "webgpu"),
+                ];
+                let searched: Vec<&str> = names
+                    .iter()
+                    .filter(|(backend, _)| backends.contains(*backend))
+                    .map(|(_, name)| *name)
+                    .collect();
+                write!(
+                    f,
+                    "no adapter found, searched backends: [{}], software fallback {}",
+                    searched.join(", "),
+                    if *fallback_attempted {
+                        "attempted"
+                    } else {
+                        "not attempted"
+                    }
+                )
+            }
+            GpuError::Device(err) => write!(f, "device error: {}", err),
+            GpuError::UnsupportedAdapter(info) => {
+                write!(f, "unsupported adapter: {} ({:?})", info.name, info.backend)
+            }
+            GpuError::Shader(filename, err) => write!(f, "{}: {}", filename, err),
+            GpuError::InputTooLong { len } => {
+                write!(f, "{} elements don't fit in u32 indices of a kernel", len)
+            }
+            GpuError::LimitExceeded {
+                limit,
+                requested,
+                max,
+            } => write!(f, "{} is {}, the device allows {}", limit, requested, max),
+            GpuError::Readback => write!(f, "reading the result back failed"),
+            GpuError::BindingCountMismatch {
+                entry,
+                expected,
+                given,
+            } => write!(
+                f,
+                "{} uses {} bindings, but binding_number is {}",
+                entry, expected, given
+            ),
+            GpuError::ShapeMismatch { shape, len } => write!(
+                f,
+                "{} elements can't be reshaped into {}x{}",
+                len, shape.0, shape.1
+            ),
+            GpuError::MisalignedReadback { bytes, elem_size } => write!(
+                f,
+                "a result of {} bytes isn't a whole number of {} byte elements",
+                bytes, elem_size
+            ),
+            GpuError::Cancelled => write!(f, "cancelled"),
+            GpuError::OutputLengthMismatch {
+                expected_bytes,
+                actual_bytes,
+            } => write!(
+                f,
+                "output buffer has {} bytes, {} were declared",
+                actual_bytes, expected_bytes
+            ),
+            GpuError::StrideOutOfBounds { stride, count, len } => write!(
+                f,
+                "{} elements with stride {} need {} input elements, there are {}",
+                count,
+                stride,
+                stride.saturating_mul(*count),
+                len
+            ),
+        }
+    }
+}
+
+impl std::error::Error for GpuError {}
+
+// `name` after `prefix`, or `default` without a prefix. `GpuConsts::label` for resources
+// created before there is a `GpuConsts`.
+fn prefixed_label(prefix: Option<&str>, name: &str, default: Option<&str>) -> Option<String> {
+    match prefix {
+        Some(prefix) => Some(format!("{}-{}", prefix, name)),
+        None => default.map(str::to_string),
+    }
+}
+
+pub struct GpuConsts {
+    instance: Arc<Instance>,
+    adapter: Adapter,
+    device: Device,
+    queue: Queue,
+    info: AdapterInfo,
+    cs_module: ShaderModule,
+    // WGSL `cs_module` was compiled from, kept to respecialize it
+    source: String,
+    // Reflected once here instead of at every dispatch
+    entries: Vec<EntryReflection>,
+    // Every module from `create_module` with its entries, by its source
+    modules: Mutex<HashMap<String, CachedModule>>,
+    pool: Arc<BufferPool>,
+    readback_path: ReadbackPath,
+    queue_info: QueueInfo,
+    label_prefix: Option<String>,
+}
+
+impl GpuConsts {
+    pub async fn initialaze(filename: &str) -> Result<GpuConsts, GpuError> {
+        GpuConsts::initialaze_with(filename, &GpuOptions::default()).await
+    }
+
+    pub async fn initialaze_with(
+        filename: &str,
+        options: &GpuOptions,
+    ) -> Result<GpuConsts, GpuError> {
+        // Instantiates instance of WebGPU, unless a shared one was given
+        let instance = options.instance.clone().unwrap_or_else(|| {
+            Arc::new(wgpu::Instance::new(wgpu::InstanceDescriptor {
+                backends: options.backends,
+                ..Default::default()
+            }))
+        });
+
+        // `request_adapter` instantiates the general connection to the GPU
+        let instance_ref = &instance;
+        let adapter = options
+            .retry
+            .run(move || async move {
+                match instance_ref
+                    .request_adapter(&wgpu::RequestAdapterOptions::default())
+                    .await
+                {
+                    Some(adapter) => Some(adapter),
+                    // Without hardware adapters a software one may still be there
+                    None => {
+                        instance_ref
+                            .request_adapter(&wgpu::RequestAdapterOptions {
+                                force_fallback_adapter: true,
+                                ..Default::default()
+                            })
+                            .await
+                    }
+                }
+            })
+            .await
+            .ok_or(GpuError::NoAdapter {
+                backends: options.backends,
+                fallback_attempted: true,
+            })?;
+
+        GpuConsts::from_adapter(instance, adapter, filename, options).await
+    }
+
+    /// `initialaze_with` on the compute-capable adapter with the most memory by
+    /// `device_memory_hint`, discrete GPUs first among equals. Software adapters are
+    /// only considered with `allow_software`.
+    pub async fn initialaze_largest_memory(
+        filename: &str,
+        options: &GpuOptions,
+        allow_software: bool,
+    ) -> Result<GpuConsts, GpuError> {
+        let instance = options.instance.clone().unwrap_or_else(|| {
+            Arc::new(wgpu::Instance::new(wgpu::InstanceDescriptor {
+                backends: options.backends,
+                ..Default::default()
+            }))
+        });
+
+        let adapter = instance
+            .enumerate_adapters(options.backends)
+            .filter(|adapter| {
+                allow_software || adapter.get_info().device_type != wgpu::DeviceType::Cpu
+            })
+            .filter(adapter_supports_compute)
+            .max_by_key(|adapter| {
+                let discrete = adapter.get_info().device_type == wgpu::DeviceType::DiscreteGpu;
+                (adapter_memory_hint(adapter), discrete)
+            })
+            .ok_or(GpuError::NoAdapter {
+                backends: options.backends,
+                fallback_attempted: allow_software,
+            })?;
+
+        GpuConsts::from_adapter(instance, adapter, filename, options).await
+    }
+
+    // The rest of `initialaze_with` once an adapter is chosen.
+    async fn from_adapter(
+        instance: Arc<Instance>,
+        adapter: Adapter,
+        filename: &str,
+        options: &GpuOptions,
+    ) -> Result<GpuConsts, GpuError> {
+        // `request_device` instantiates the feature specific connection to the GPU, defining some parameters,
+        //  `features` being the available features.
+        let (device, queue) = adapter
+            .request_device(
+                &wgpu::DeviceDescriptor {
+                    label: prefixed_label(options.label_prefix.as_deref(), "device", None)
+                        .as_deref(),
+                    // Both are optional, `BufCoder` only uses them when available
+                    features: adapter.features()
+                        & (wgpu::Features::TIMESTAMP_QUERY
+                            | wgpu::Features::MAPPABLE_PRIMARY_BUFFERS),
+                    limits: wgpu::Limits::downlevel_defaults(),
+                },
+                None,
+            )
+            .await
+            .map_err(GpuError::Device)?;
+
+        let info = adapter.get_info();
+
+        if info.vendor == 0x10005 {
+            return Err(GpuError::UnsupportedAdapter(info));
+        }
+
+        let source =
+            read_to_string(filename).map_err(|err| GpuError::Shader(filename.to_string(), err))?;
+        let cs_module = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: prefixed_label(options.label_prefix.as_deref(), "shader", None).as_deref(),
+            source: wgpu::ShaderSource::Wgsl(source.as_str().into()),
+        });
+
+        let readback_path = select_readback_path(&info, device.features());
+
+        // wgpu creates exactly one queue per device and can't choose its family or priority,
+        // so any preference falls back to that queue
+        let queue_info = QueueInfo {
+            requested: options.queue_preference,
+            applied: options.queue_preference == QueuePreference::Default,
+        };
+
+        Ok(GpuConsts {
+            instance,
+            adapter,
+            device,
+            queue,
+            readback_path,
+            info,
+            cs_module,
+            entries: reflect_entries(&source),
+            modules: Mutex::new(HashMap::new()),
+            source,
+            pool: Arc::new(BufferPool::new(options.pool_capacity)),
+            queue_info,
+            label_prefix: options.label_prefix.clone(),
+        })
+    }
+
+    // `name` after `GpuOptions::label_prefix`, or `default` without a prefix.
+    fn label(&self, name: &str, default: Option<&str>) -> Option<String> {
+        prefixed_label(self.label_prefix.as_deref(), name, default)
+    }
+
+    pub fn readback_path(&self) -> ReadbackPath {
+        self.readback_path
+    }
+
+    /// The instance of this context, to share with further ones in `GpuOptions::instance`.
+    pub fn instance(&self) -> &Arc<Instance> {
+        &self.instance
+    }
+
+    /// Which queue was asked for in `GpuOptions` and whether it was granted.
+    pub fn queue_info(&self) -> QueueInfo {
+        self.queue_info
+    }
+
+    /// Adapter, features and limits in one `CapabilityReport`.
+    pub fn capability_report(&self) -> CapabilityReport {
+        CapabilityReport {
+            info: self.info.clone(),
+            features: self.device.features(),
+            limits: self.device.limits(),
+            queue: self.queue_info,
+            readback_path: self.readback_path,
+        }
+    }
+
+    /// Bindings `entry` of the main shader needs, see `reflect_binding_count`.
+    pub fn binding_count(&self, entry: &str) -> Option<u32> {
+        self.reflection(entry)
+            .map(|reflection| reflection.binding_count)
+    }
+
+    fn reflection(&self, entry: &str) -> Option<&EntryReflection> {
+        self.entries
+            .iter()
+            .find(|reflection| reflection.name == entry)
+    }
+
+    /// Compiles `entry` of the main shader once, for any number of `Stage::from_kernel`s.
+    /// `None` when the shader has no such entry point with a literal `@workgroup_size`.
+    pub fn compile_kernel(&self, entry: &str) -> Option<Kernel> {
+        self.compile_kernel_with_layout(entry, None)
+    }
+
+    /// `compile_kernel` with an explicit layout whose binding `i` of group 0 is visible to
+    /// `visibility[i]` instead of only `COMPUTE`, e.g. to share the buffers with a later
+    /// render pass. Bindings past `visibility` stay `COMPUTE`; `COMPUTE` is always added.
+    /// Making a writable binding visible to `FRAGMENT` or `VERTEX` needs the device support.
+    pub fn compile_kernel_with_visibility(
+        &self,
+        entry: &str,
+        visibility: &[wgpu::ShaderStages],
+    ) -> Option<Kernel> {
+        let reflection = self.reflection(entry)?;
+
+        let entries: Vec<wgpu::BindGroupLayoutEntry> = (0..reflection.binding_count)
+            .map(|binding| wgpu::BindGroupLayoutEntry {
+                binding,
+                visibility: visibility
+                    .get(binding as usize)
+                    .map_or(wgpu::ShaderStages::COMPUTE, |&stages| {
+                        stages | wgpu::ShaderStages::COMPUTE
+                    }),
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Storage {
+                        read_only: reflection.read_only[binding as usize],
+                    },
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            })
+            .collect();
+        let bind_group_layout =
+            self.device
+                .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                    label: self
+                        .label(&format!("kernel-bind-group-layout-{}", entry), Some(entry))
+                        .as_deref(),
+                    entries: &entries,
+                });
+
+        // The params uniform keeps its compute-only layout
+        let params_layout = reflection.uses_params.then(|| {
+            self.device
+                .create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                    label: self
+                        .label("params-layout", Some("Params Layout"))
+                        .as_deref(),
+                    entries: &[wgpu::BindGroupLayoutEntry {
+                        binding: 0,
+                        visibility: wgpu::ShaderStages::COMPUTE,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                        count: None,
+                    }],
+                })
+        });
+        let mut bind_group_layouts = vec![&bind_group_layout];
+        bind_group_layouts.extend(&params_layout);
+
+        let layout = self
+            .device
+            .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: self
+                    .label(&format!("kernel-layout-{}", entry), Some(entry))
+                    .as_deref(),
+                bind_group_layouts: &bind_group_layouts,
+                push_constant_ranges: &[],
+            });
+
+        self.compile_kernel_with_layout(entry, Some((&layout, bind_group_layout)))
+    }
+
+    // `compile_kernel` with the explicit `layout` and its bind group 0 layout, when given.
+    fn compile_kernel_with_layout(
+        &self,
+        entry: &str,
+        layout: Option<(&wgpu::PipelineLayout, BindGroupLayout)>,
+    ) -> Option<Kernel> {
+        let workgroup_size = reflect_workgroup_size(&self.source, entry)?;
+        let pipeline = self
+            .device
+            .create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: self
+                    .label(&format!("kernel-{}", entry), Some(entry))
+                    .as_deref(),
+                layout: layout.as_ref().map(|(layout, _)| *layout),
+                module: &self.cs_module,
+                entry_point: entry,
+            });
+        let bind_group_layout = match layout {
+            Some((_, bind_group_layout)) => bind_group_layout,
+            None => pipeline.get_bind_group_layout(0),
+        };
+
+        Some(Kernel {
+            entry: entry.to_string(),
+            workgroup_size,
+            pipeline,
+            bind_group_layout,
+        })
+    }
+
+    /// A `ReadbackRing` of `slots` buffers of `slot_size` bytes on this device.
+    pub fn readback_ring(&self, slots: usize, slot_size: BufferAddress) -> ReadbackRing {
+        ReadbackRing::new(self, slots, slot_size)
+    }
+
+    /// Buffers of dropped `BufCoder`s that new ones reuse.
+    pub fn buffer_pool(&self) -> &BufferPool {
+        &self.pool
+    }
+
+    /// Name, backend and type of the adapter the device was created on.
+    pub fn adapter_info(&self) -> &AdapterInfo {
+        &self.info
+    }
+
+    /// Whether the adapter runs compute shaders at all, which some downlevel (GL, browser
+    /// or software) adapters don't; without them creating any compute pipeline fails.
+    pub fn supports_compute(&self) -> bool {
+        adapter_supports_compute(&self.adapter)
+    }
+
+    /// Lower bound of the device memory in bytes: the largest buffer the adapter allows,
+    /// since wgpu 0.17 has no query for the total memory on any backend.
+    /// `None` when the adapter doesn't bound its buffers at all.
+    pub fn device_memory_hint(&self) -> Option<u64> {
+        adapter_memory_hint(&self.adapter)
+    }
+
+    /// Compiles WGSL `source` on this device, for stages that don't use the main shader.
+    /// Modules are kept by source, so calling this again with the same source, as the
+    /// `gpu_*` wrappers do, returns the first module, with its label, without recompiling.
+    pub fn create_module(&self, label: &str, source: &str) -> Arc<ShaderModule> {
+        let mut modules = self.modules.lock().unwrap();
+        let cached = modules
+            .entry(source.to_string())
+            .or_insert_with(|| CachedModule {
+                module: Arc::new(
+                    self.device
+                        .create_shader_module(wgpu::ShaderModuleDescriptor {
+                            label: self.label(label, Some(label)).as_deref(),
+                            source: wgpu::ShaderSource::Wgsl(source.into()),
+                        }),
+                ),
+                entries: reflect_entries(source),
+            });
+
+        cached.module.clone()
+    }
+
+    // Whether `entry` of `module`, the main shader when `None`, declares the params uniform.
+    // Modules that didn't come from `create_module` are unknown and get no lengths.
+    fn uses_params(&self, module: Option<&ShaderModule>, entry: &str) -> bool {
+        let uses = |entries: &[EntryReflection]| {
+            entries
+                .iter()
+                .any(|reflection| reflection.name == entry && reflection.uses_params)
+        };
+        match module {
+            None => uses(&self.entries),
+            Some(module) => self
+                .modules
+                .lock()
+                .unwrap()
+                .values()
+                .find(|cached| cached.module.global_id() == module.global_id())
+                .is_some_and(|cached| uses(&cached.entries)),
+        }
+    }
+
+    /// Times `entry` at every workgroup size of `candidates` and returns the fastest one.
+    /// `entry` must use the layout of `sum_func.wgsl` and stay correct at any workgroup size.
+    /// `flat_index` assumes 256 invocations per workgroup past the first row of the grid, so
+    /// the sizes that can't cover `data` in one row are skipped; the first candidate is
+    /// returned when none can.
+    pub fn autotune_workgroup_size(&self, entry: &str, data: &[u32], candidates: &[u32]) -> u32 {
+        assert!(!candidates.is_empty(), "no workgroup size candidates");
+        // Empty buffers can't be bound, and every size is equally fast at doing nothing
+        if data.is_empty() {
+            return candidates[0];
+        }
+
+        let run_at = |module: &ShaderModule, dispatch: Dispatch| {
+            let mut bindings = Bindings::initialize_two(vec![0; data.len()], data.to_vec());
+            let stage = Stage::new(entry, 2).dispatch(dispatch).module(module);
+            let bc = BufCoder::initialize_stages(self, &mut bindings, &[stage]);
+            pollster::block_on(self.run::<u32>(&bc)).unwrap();
+        };
+
+        let mut best = (Duration::MAX, candidates[0]);
+        for &size in candidates {
+            let plan = self.plan_dispatch(workgroup_count_for(data.len(), size) as u64);
+            let Some(plan) = plan.ok().filter(|plan| plan.dimensions == 1) else {
+                continue;
+            };
+            let module =
+                self.create_module("autotune", &specialize_workgroup_size(&self.source, size));
+
+            // The first run pays for compiling the pipeline, only the second one is timed
+            run_at(&module, plan.dispatch());
+            let start = Instant::now();
+            run_at(&module, plan.dispatch());
+            let time = start.elapsed();
+
+            if time < best.0 {
+                best = (time, size);
+            }
+        }
+
+        best.1
+    }
+
+    /// Compiles the pipeline for `entry` and runs a throwaway dispatch, so that driver
+    /// initialization and shader compilation are not attributed to the first timed run.
+    pub fn warmup(&self, entry: &str) {
+        // Creating the pipeline makes the driver compile `entry`, the result lands in its cache.
+        self.device
+            .create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: self.label("warmup-pipeline", Some("warmup")).as_deref(),
+                layout: None,
+                module: &self.cs_module,
+                entry_point: entry,
+            });
+
+        // An empty kernel without bindings, only to get one submission through the queue.
+        let module = self
+            .device
+            .create_shader_module(wgpu::ShaderModuleDescriptor {
+                label: self.label("warmup-shader", Some("warmup")).as_deref(),
+                source: wgpu::ShaderSource::Wgsl(include_str!("warmup_func.wgsl").into()),
+            });
+        let pipeline = self
+            .device
+            .create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: self.label("warmup", Some("warmup")).as_deref(),
+                layout: None,
+                module: &module,
+                entry_point: "warmup_call",
+            });
+
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+        {
+            let mut cpass =
+                encoder.begin_compute_pass(&wgpu::ComputePassDescriptor { label: None });
+            cpass.set_pipeline(&pipeline);
+            cpass.dispatch_workgroups(1, 1, 1);
+        }
+        self.queue.submit(Some(encoder.finish()));
+        self.device.poll(wgpu::Maintain::Wait);
+    }
+
+    /// Blocks until the work submitted by `bufcoder` is done, without waiting for
+    /// anything submitted after it.
+    pub fn wait_for(&self, bufcoder: &BufCoder) {
+        self.device.poll(wgpu::Maintain::WaitForSubmissionIndex(
+            bufcoder.submission_index.clone(),
+        ));
+    }
+
+    /// Grid of at least `workgroups_needed` workgroups, with none of its dimensions over
+    /// `max_compute_workgroups_per_dimension`: 1D when that fits, else 2D, else 3D.
+    /// `LimitExceeded` when not even 3D fits.
+    pub fn plan_dispatch(&self, workgroups_needed: u64) -> Result<DispatchPlan, GpuError> {
+        let max = self.device.limits().max_compute_workgroups_per_dimension as u64;
+        if workgroups_needed > max.pow(3) {
+            return Err(GpuError::LimitExceeded {
+                limit: "max_compute_workgroups_per_dimension",
+                requested: workgroups_needed,
+                max: max.pow(3),
+            });
+        }
+
+        // Every higher dimension is as short as possible, x takes what is left
+        let z = workgroups_needed.div_ceil(max * max).max(1);
+        let per_layer = workgroups_needed.div_ceil(z);
+        let y = per_layer.div_ceil(max).max(1);
+        let x = per_layer.div_ceil(y);
+        let dimensions = if z > 1 {
+            3
+        } else if y > 1 {
+            2
+        } else {
+            1
+        };
+
+        Ok(DispatchPlan {
+            workgroups: (x as u32, y as u32, z as u32),
+            dimensions,
+        })
+    }
+
+    /// `plan_dispatch` of the 256-invocation workgroups that give every one of `len` elements
+    /// its own invocation. The kernel finds its element with `flat_index`, see `vec_func.wgsl`.
+    pub fn dispatch_for(&self, len: usize) -> Result<Dispatch<'static>, GpuError> {
+        Ok(self
+            .plan_dispatch(len.div_ceil(256).max(1) as u64)?
+            .dispatch())
+    }
+
+    /// Checks up front that a 256-invocation kernel over `data_len` elements with `bindings`
+    /// storage buffers fits the device limits, and names the first one it doesn't.
+    pub fn can_run(&self, data_len: usize, bindings: usize) -> Result<(), GpuError> {
+        check_len(data_len)?;
+        self.dispatch_for(data_len)?;
+
+        let limits = self.device.limits();
+        let checks = [
+            (
+                "max_storage_buffer_binding_size",
+                (data_len * std::mem::size_of::<u32>()) as u64,
+                limits.max_storage_buffer_binding_size as u64,
+            ),
+            (
+                "max_storage_buffers_per_shader_stage",
+                bindings as u64,
+                limits.max_storage_buffers_per_shader_stage as u64,
+            ),
+        ];
+
+        match checks.iter().find(|(_, requested, max)| requested > max) {
+            Some(&(limit, requested, max)) => Err(GpuError::LimitExceeded {
+                limit,
+                requested,
+                max,
+            }),
+            None => Ok(()),
+        }
+    }
+
+    /// Blocks until everything submitted so far, by any `BufCoder`, is done.
+    pub fn wait_idle(&self) {
+        self.device.poll(wgpu::Maintain::Wait);
+    }
+
+    /// GPU time from the start of the first to the end of the last stage of `bufcoder`,
+    /// `None` when the device doesn't support `TIMESTAMP_QUERY`.
+    pub async fn compute_time(&self, bufcoder: &BufCoder) -> Option<Duration> {
+        let timestamps = bufcoder.timestamps.as_ref()?;
+
+        let ticks = self
+            .map_staging(&timestamps.readback_buffer, |data| {
+                let ticks: &[u64] = bytemuck::cast_slice(data);
+                ticks[1].saturating_sub(ticks[0])
+            })
+            .await;
+
+        // A tick lasts `get_timestamp_period` nanoseconds
+        let nanos = ticks as f64 * self.queue.get_timestamp_period() as f64;
+        Some(Duration::from_nanos(nanos as u64))
+    }
+
+    /// `run` together with `compute_time`, which is `None` without `TIMESTAMP_QUERY`.
+    pub async fn run_timed(
+        &self,
+        bufcoder: &BufCoder,
+    ) -> Result<(Vec<u32>, Option<Duration>), GpuError> {
+        let result = self.run::<u32>(bufcoder).await?;
+        let time = self.compute_time(bufcoder).await;
+
+        Ok((result, time))
+    }
+
+    /// Like `run`, but also reports the upload, compute and download times of `bufcoder`.
+    pub async fn run_with_timings(&self, bufcoder: &BufCoder) -> Option<(Vec<u32>, PhaseTimings)> {
+        // Waits for the stages and the copy into the staging buffer first,
+        // so that the readback below only measures the download.
+        let start = Instant::now();
+        self.wait_for(bufcoder);
+        let waited = start.elapsed();
+
+        let (compute, gpu_timestamps) = match self.compute_time(bufcoder).await {
+            Some(compute) => (compute, true),
+            None => (waited, false),
+        };
+
+        let start = Instant::now();
+        let result = self.run::<u32>(bufcoder).await.ok()?;
+        let download = start.elapsed();
+
+        Some((
+            result,
+            PhaseTimings {
+                upload: bufcoder.upload_time,
+                compute,
+                download,
+                gpu_timestamps,
+            },
+        ))
+    }
+
+    /// Reads the result back as `T`, which has to match the element type of the kernel's
+    /// output array, e.g. `run::<f32>` for `array<f32>` or `run::<i32>` for `array<i32>`.
+    /// `MisalignedReadback` when the output isn't a whole number of `T`.
+    pub async fn run<T: bytemuck::Pod>(&self, bufcoder: &BufCoder) -> Result<Vec<T>, GpuError> {
+        // Copies the mapped view into an owned `Vec`
+        self.map_staging(bufcoder.readback_buffer(), cast_readback::<T>)
+            .await
+    }
+
+    /// `run` for a kernel declared to output `len` elements of `T`, e.g. by a pass of a
+    /// multi-pass algorithm that shrinks the data: `OutputLengthMismatch` instead of reading
+    /// a buffer of any other size. Checked before anything is mapped.
+    pub async fn run_expecting<T: bytemuck::Pod>(
+        &self,
+        bufcoder: &BufCoder,
+        len: usize,
+    ) -> Result<Vec<T>, GpuError> {
+        let expected = (len * std::mem::size_of::<T>()) as u64;
+        let actual = bufcoder.readback_buffer().size();
+        if expected != actual {
+            return Err(GpuError::OutputLengthMismatch {
+                expected_bytes: expected,
+                actual_bytes: actual,
+            });
+        }
+
+        self.run::<T>(bufcoder).await
+    }
+
+    /// `run` that gives up with `Cancelled` once `token` is cancelled, checked before the
+    /// readback starts and while it waits. Submitted work can't be stopped, but the caller
+    /// isn't blocked on it: the device is polled without waiting in between the checks,
+    /// which back off from 10µs to 1ms apart so a long wait doesn't keep a core busy. The
+    /// pauses are awaited with `backoff_sleep`, the executor thread isn't blocked by them.
+    pub async fn run_cancellable<T: bytemuck::Pod>(
+        &self,
+        bufcoder: &BufCoder,
+        token: &CancelToken,
+    ) -> Result<Vec<T>, GpuError> {
+        if token.is_cancelled() {
+            return Err(GpuError::Cancelled);
+        }
+
+        let buffer = bufcoder.readback_buffer();
+        let buffer_slice = buffer.slice(..);
+        // The receiver is gone when the mapping completes after a cancellation
+        let (sender, receiver) = std::sync::mpsc::channel();
+        buffer_slice.map_async(wgpu::MapMode::Read, move |v| {
+            let _ = sender.send(v);
+        });
+
+        let mut backoff = Duration::from_micros(10);
+        loop {
+            if token.is_cancelled() {
+                // Aborts the pending mapping, so the buffer can be mapped again later
+                buffer.unmap();
+                return Err(GpuError::Cancelled);
+            }
+            self.device.poll(wgpu::Maintain::Poll);
+            match receiver.try_recv() {
+                Ok(Ok(())) => break,
+                Ok(Err(_)) | Err(std::sync::mpsc::TryRecvError::Disconnected) => {
+                    return Err(GpuError::Readback)
+                }
+                Err(std::sync::mpsc::TryRecvError::Empty) => {
+                    backoff_sleep(backoff).await;
+                    backoff = (backoff * 2).min(Duration::from_millis(1));
+                }
+            }
+        }
+
+        let result = cast_readback(&buffer_slice.get_mapped_range());
+        buffer.unmap();
+        result
+    }
+
+    /// `run` for kernels that modify binding 0 in place: the result is written back into
+    /// `bindings.input_output` instead of a new `Vec`.
+    pub async fn run_in_place(&self, bufcoder: &BufCoder, bindings: &mut Bindings) {
+        self.run_with(bufcoder, |data| {
+            bindings.input_output.clear();
+            bindings.input_output.extend_from_slice(data);
+        })
+        .await
+    }
+
+    /// `run` into `out`, which is cleared and refilled, so a `Vec` reused between runs of
+    /// the same size doesn't allocate again.
+    pub async fn run_into_slice(&self, bufcoder: &BufCoder, out: &mut Vec<u32>) {
+        self.run_with(bufcoder, |data| {
+            out.clear();
+            out.extend_from_slice(data);
+        })
+        .await
+    }
+
+    /// `run` reshaped into a row-major `shape.0 x shape.1` array, e.g. for matmul output.
+    #[cfg(feature = "ndarray")]
+    pub async fn run_as_array2(
+        &self,
+        bufcoder: &BufCoder,
+        shape: (usize, usize),
+    ) -> Result<ndarray::Array2<u32>, GpuError> {
+        let data = self.run_with(bufcoder, |data| data.to_vec()).await;
+        let len = data.len();
+
+        ndarray::Array2::from_shape_vec(shape, data)
+            .map_err(|_| GpuError::ShapeMismatch { shape, len })
+    }
+
+    /// Runs `stage` once over each of `bindings`, submitting the next run while earlier ones
+    /// compute, but with at most `max_in_flight` results waiting on the GPU: before going
+    /// over that, the oldest one is waited for and read back. Results in input order.
+    pub fn run_many(
+        &self,
+        stage: Stage,
+        bindings: Vec<Bindings>,
+        max_in_flight: usize,
+    ) -> Vec<Vec<u32>> {
+        self.run_many_observed(stage, bindings, max_in_flight, |_| {})
+    }
+
+    // `run_many` that reports the number of runs in flight after every submission.
+    fn run_many_observed(
+        &self,
+        stage: Stage,
+        bindings: Vec<Bindings>,
+        max_in_flight: usize,
+        mut submitted: impl FnMut(usize),
+    ) -> Vec<Vec<u32>> {
+        assert!(
+            max_in_flight > 0,
+            "at least one submission has to be in flight"
+        );
+
+        let mut results = Vec::with_capacity(bindings.len());
+        let mut in_flight = std::collections::VecDeque::with_capacity(max_in_flight);
+        for mut numbers in bindings {
+            if in_flight.len() == max_in_flight {
+                let oldest: BufCoder = in_flight.pop_front().unwrap();
+                results.push(pollster::block_on(self.run::<u32>(&oldest)).unwrap());
+            }
+            in_flight.push_back(BufCoder::initialize_stages(self, &mut numbers, &[stage]));
+            submitted(in_flight.len());
+        }
+        for bufcoder in in_flight {
+            results.push(pollster::block_on(self.run::<u32>(&bufcoder)).unwrap());
+        }
+
+        results
+    }
+
+    /// Creates everything `func_name` of the main shader needs to run over `bindings` with
+    /// `workgroups` once, so `PreparedDispatch::run` only uploads the input and dispatches.
+    /// Entry points using the params uniform can't be prepared.
+    pub fn prepare_dispatch(
+        &self,
+        bindings: &Bindings,
+        func_name: &str,
+        binding_number: u32,
+        workgroups: (u32, u32, u32),
+    ) -> PreparedDispatch {
+        let usage = wgpu::BufferUsages::STORAGE
+            | wgpu::BufferUsages::COPY_DST
+            | wgpu::BufferUsages::COPY_SRC;
+        let storage_buffers: Vec<Buffer> = [
+            &bindings.input_output,
+            &bindings.shared_memory,
+            &bindings.global_memory,
+            &bindings.output_vec,
+        ]
+        .iter()
+        .take(binding_number as usize)
+        .map(|contents| {
+            self.device
+                .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                    label: self.label("prepared-storage", None).as_deref(),
+                    contents: bytemuck::cast_slice(contents),
+                    usage,
+                })
+        })
+        .collect();
+        let staging_buffer = self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: self
+                .label("prepared-staging", Some("Staging Buffer"))
+                .as_deref(),
+            size: std::mem::size_of_val(bindings.input_output.as_slice()) as BufferAddress,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let pipeline = self
+            .device
+            .create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: self
+                    .label(&format!("prepared-pipeline-{}", func_name), Some(func_name))
+                    .as_deref(),
+                layout: None,
+                module: &self.cs_module,
+                entry_point: func_name,
+            });
+        let entries: Vec<BindGroupEntry> = storage_buffers
+            .iter()
+            .enumerate()
+            .map(|(binding, buffer)| wgpu::BindGroupEntry {
+                binding: binding as u32,
+                resource: buffer.as_entire_binding(),
+            })
+            .collect();
+        let bind_group = self.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: self
+                .label(&format!("prepared-bind-group-{}", func_name), None)
+                .as_deref(),
+            layout: &pipeline.get_bind_group_layout(0),
+            entries: &entries,
+        });
+
+        PreparedDispatch {
+            pipeline,
+            bind_group,
+            storage_buffers,
+            staging_buffer,
+            workgroups,
+        }
+    }
+
+    /// `run` as an iterator over the mapped result, without collecting it into a `Vec` first.
+    pub async fn run_iter<'a>(&self, bufcoder: &'a BufCoder) -> Result<ResultIter<'a>, GpuError> {
+        let buffer = bufcoder.readback_buffer();
+        if !self.map_for_read(buffer).await {
+            return Err(GpuError::Readback);
+        }
+
+        Ok(ResultIter {
+            view: Some(buffer.slice(..).get_mapped_range()),
+            buffer,
+            next: 0,
+        })
+    }
+
+    /// Passes a borrowed view of the mapped result to `f` instead of copying it into a `Vec`.
+    /// The staging buffer is unmapped right after `f` returns.
+    pub async fn run_with<R>(&self, bufcoder: &BufCoder, f: impl FnOnce(&[u32]) -> R) -> R {
+        // Since contents are got in bytes, this views these bytes as u32
+        self.map_staging(bufcoder.readback_buffer(), |data| {
+            f(bytemuck::cast_slice(data))
+        })
+        .await
+    }
+
+    /// Runs `entry` with `data` as a `width` texels wide `R32Uint` texture instead of a buffer,
+    /// see `texture_func.wgsl`: the input is sampled at binding 1, the output is the storage
+    /// texture at binding 0. The last row is padded with zeros, which are not returned.
+    /// `None` when `data` is empty or the texture would exceed the device limits.
+    pub async fn run_texture(&self, entry: &str, data: &[u32], width: u32) -> Option<Vec<u32>> {
+        let max_dimension = self.device.limits().max_texture_dimension_2d;
+        if data.is_empty() || width == 0 || width > max_dimension {
+            return None;
+        }
+        let height = data.len().div_ceil(width as usize) as u32;
+        if height > max_dimension {
+            return None;
+        }
+
+        let mut texels = data.to_vec();
+        texels.resize((width * height) as usize, 0);
+
+        let extent = wgpu::Extent3d {
+            width,
+            height,
+            depth_or_array_layers: 1,
+        };
+        let create_texture = |label, usage| {
+            self.device.create_texture(&wgpu::TextureDescriptor {
+                label: self.label(label, Some(label)).as_deref(),
+                size: extent,
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format: wgpu::TextureFormat::R32Uint,
+                usage,
+                view_formats: &[],
+            })
+        };
+        let input = create_texture(
+            "Input Texture",
+            wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+        );
+        let output = create_texture(
+            "Output Texture",
+            wgpu::TextureUsages::STORAGE_BINDING | wgpu::TextureUsages::COPY_SRC,
+        );
+
+        self.queue.write_texture(
+            input.as_image_copy(),
+            bytemuck::cast_slice(&texels),
+            wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(width * 4),
+                rows_per_image: Some(height),
+            },
+            extent,
+        );
+
+        // Rows of a texture to buffer copy have to start at multiples of 256 bytes
+        let padded_row = (width * 4).div_ceil(wgpu::COPY_BYTES_PER_ROW_ALIGNMENT)
+            * wgpu::COPY_BYTES_PER_ROW_ALIGNMENT;
+        let staging_buffer = self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: self.label("texture-staging", None).as_deref(),
+            size: (padded_row * height) as BufferAddress,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let pipeline = self
+            .device
+            .create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: self
+                    .label(&format!("texture-pipeline-{}", entry), None)
+                    .as_deref(),
+                layout: None,
+                module: &self.cs_module,
+                entry_point: entry,
+            });
+        let output_view = output.create_view(&wgpu::TextureViewDescriptor::default());
+        let input_view = input.create_view(&wgpu::TextureViewDescriptor::default());
+        let bind_group = self.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: self
+                .label(&format!("texture-bind-group-{}", entry), None)
+                .as_deref(),
+            layout: &pipeline.get_bind_group_layout(0),
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: wgpu::BindingResource::TextureView(&output_view),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(&input_view),
+                },
+            ],
+        });
+
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+        {
+            let mut cpass =
+                encoder.begin_compute_pass(&wgpu::ComputePassDescriptor { label: None });
+            cpass.set_pipeline(&pipeline);
+            cpass.set_bind_group(0, &bind_group, &[]);
+            cpass.insert_debug_marker(entry);
+            // One invocation per texel, in the 16x16 workgroups of `texture_func.wgsl`
+            cpass.dispatch_workgroups(width.div_ceil(16), height.div_ceil(16), 1);
+        }
+        encoder.copy_texture_to_buffer(
+            output.as_image_copy(),
+            wgpu::ImageCopyBuffer {
+                buffer: &staging_buffer,
+                layout: wgpu::ImageDataLayout {
+                    offset: 0,
+                    bytes_per_row: Some(padded_row),
+                    rows_per_image: Some(height),
+                },
+            },
+            extent,
+        );
+        self.queue.submit(Some(encoder.finish()));
+
+        let mut result = self
+            .map_staging(&staging_buffer, |data| {
+                // Drops the row padding
+                data.chunks(padded_row as usize)
+                    .flat_map(|row| bytemuck::cast_slice::<u8, u32>(&row[..(width * 4) as usize]))
+                    .copied()
+                    .collect::<Vec<u32>>()
+            })
+            .await;
+        result.truncate(data.len());
+
+        Some(result)
+    }
+
+    /// Writes the raw bytes of the result straight from the mapped buffer into `w`,
+    /// without collecting them into a `Vec` first.
+    pub async fn run_to_writer<W: std::io::Write>(
+        &self,
+        bufcoder: &BufCoder,
+        w: &mut W,
+    ) -> std::io::Result<()> {
+        self.map_staging(bufcoder.readback_buffer(), |data| w.write_all(data))
+            .await
+    }
+
+    /// Reads back the buffer at `binding` of `bufcoder`, not only the primary one.
+    /// Needs its own copy and submission, the staging buffer of `bufcoder` only holds binding 0.
+    pub async fn read_binding(&self, bufcoder: &BufCoder, binding: usize) -> Option<Vec<u32>> {
+        Some(self.read_buffer(bufcoder.binding_buffer(binding)).await)
+    }
+
+    /// Element 0 of binding 0, e.g. the result of a reduction, copying only its 4 bytes
+    /// instead of mapping the whole output. `OutputLengthMismatch` when binding 0 is
+    /// shorter than one element. With `BufCoder::initialize_stages_without_readback` the
+    /// whole output isn't copied at all.
+    pub async fn read_scalar(&self, bufcoder: &BufCoder) -> Result<u32, GpuError> {
+        let staging_buffer = self.copy_scalar(bufcoder)?;
+
+        Ok(self
+            .map_staging(&staging_buffer, bytemuck::pod_read_unaligned::<u32>)
+            .await)
+    }
+
+    // Submits the copy of element 0 of binding 0 and returns the buffer it lands in.
+    fn copy_scalar(&self, bufcoder: &BufCoder) -> Result<Buffer, GpuError> {
+        let size = std::mem::size_of::<u32>() as BufferAddress;
+        let actual = bufcoder.storage_buffer().size();
+        if actual < size {
+            return Err(GpuError::OutputLengthMismatch {
+                expected_bytes: size,
+                actual_bytes: actual,
+            });
+        }
+
+        let staging_buffer = self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: self.label("scalar-staging", None).as_deref(),
+            size,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+        encoder.copy_buffer_to_buffer(bufcoder.storage_buffer(), 0, &staging_buffer, 0, size);
+        self.queue.submit(Some(encoder.finish()));
+
+        Ok(staging_buffer)
+    }
+
+    /// `read_binding` as elements of `T`, `MisalignedReadback` when the buffer isn't a whole
+    /// number of them, e.g. `f32` output next to `u32` inputs set with `Bindings::set_binding`.
+    pub async fn read_binding_as<T: bytemuck::Pod>(
+        &self,
+        bufcoder: &BufCoder,
+        binding: usize,
+    ) -> Result<Vec<T>, GpuError> {
+        let words = self.read_buffer(bufcoder.binding_buffer(binding)).await;
+        let bytes: &[u8] = bytemuck::cast_slice(&words);
+
+        let elem_size = std::mem::size_of::<T>();
+        if elem_size == 0 || !bytes.len().is_multiple_of(elem_size) {
+            return Err(GpuError::MisalignedReadback {
+                bytes: bytes.len(),
+                elem_size,
+            });
+        }
+        Ok(bytes
+            .chunks_exact(elem_size)
+            .map(bytemuck::pod_read_unaligned)
+            .collect())
+    }
+
+    // Copies `buffer`, which has to allow `COPY_SRC`, through a new staging buffer.
+    async fn read_buffer(&self, buffer: &Buffer) -> Vec<u32> {
+        let size = buffer.size();
+
+        // An unused binding has no contents, and an empty buffer can't be mapped
+        if size == 0 {
+            return Vec::new();
+        }
+
+        let staging_buffer = self.device.create_buffer(&wgpu::BufferDescriptor {
+            label: self.label("read-staging", None).as_deref(),
+            size,
+            usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+        encoder.copy_buffer_to_buffer(buffer, 0, &staging_buffer, 0, size);
+        self.queue.submit(Some(encoder.finish()));
+
+        self.map_staging(&staging_buffer, |data| bytemuck::cast_slice(data).to_vec())
+            .await
+    }
+
+    async fn map_staging<R>(&self, staging_buffer: &Buffer, f: impl FnOnce(&[u8]) -> R) -> R {
+        // Note that we're not calling `.await` here.
+        let buffer_slice = staging_buffer.slice(..);
+
+        if self.map_for_read(staging_buffer).await {
+            // Gets contents of buffer
+            let data = buffer_slice.get_mapped_range();
+            let result = f(&data);
+
+            // With the current interface, we have to make sure all mapped views are
+            // dropped before we unmap the buffer.
+            drop(data);
+            staging_buffer.unmap(); // Unmaps buffer from memory
+                                    // If you are familiar with C++ these 2 lines can be thought of similarly to:
+                                    //   delete myPointer;
+                                    //   myPointer = NULL;
+                                    // It effectively frees the memory
+
+            // Returns what `f` computed from the buffer
+            result
+        } else {
+            panic!("failed to run compute on gpu!")
+        }
+    }
+
+    // Maps all of `staging_buffer` for reading, `false` when that failed.
+    async fn map_for_read(&self, staging_buffer: &Buffer) -> bool {
+        // Sets the buffer up for mapping, sending over the result of the mapping back to us when it is finished.
+        let (sender, receiver) = futures_intrusive::channel::shared::oneshot_channel();
+        staging_buffer
+            .slice(..)
+            .map_async(wgpu::MapMode::Read, move |v| sender.send(v).unwrap());
+
+        // Poll the device in a blocking manner so that our future resolves.
+        // In an actual application, `device.poll(...)` should
+        // be called in an event loop or on another thread.
+        self.device.poll(wgpu::Maintain::Wait);
+
+        // Awaits until `buffer_future` can be read from
+        matches!(receiver.receive().await, Some(Ok(())))
+    }
+}
+
+// The mapped bytes of a result as `T`, `MisalignedReadback` instead of the panic of
+// `cast_slice` when they aren't a whole number of `T`.
+fn cast_readback<T: bytemuck::Pod>(data: &[u8]) -> Result<Vec<T>, GpuError> {
+    let elem_size = std::mem::size_of::<T>();
+    if elem_size == 0 || !data.len().is_multiple_of(elem_size) {
+        return Err(GpuError::MisalignedReadback {
+            bytes: data.len(),
+            elem_size,
+        });
+    }
+    Ok(bytemuck::cast_slice(data).to_vec())
+}
+
+/// Lets another thread, e.g. of a UI, abandon work: `BufCoder::initialize_cancellable`
+/// doesn't submit and `GpuConsts::run_cancellable` stops waiting once it is cancelled.
+/// Clones share the same state.
+#[derive(Clone, Debug, Default)]
+pub struct CancelToken(Arc<std::sync::atomic::AtomicBool>);
+
+impl CancelToken {
+    pub fn new() -> Self {
+        CancelToken::default()
+    }
+
+    pub fn cancel(&self) {
+        self.0.store(true, std::sync::atomic::Ordering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(std::sync::atomic::Ordering::Relaxed)
+    }
+}
+
+/// The result of `GpuConsts::run_iter`, read straight from the mapped buffer, which stays
+/// mapped until the iterator is dropped. `bufcoder` can't be run again before that.
+pub struct ResultIter<'a> {
+    view: Option<wgpu::BufferView<'a>>,
+    buffer: &'a Buffer,
+    next: usize,
+}
+
+impl Iterator for ResultIter<'_> {
+    type Item = u32;
+
+    fn next(&mut self) -> Option<u32> {
+        let view = self.view.as_ref()?;
+        let bytes = view.get(self.next..self.next + std::mem::size_of::<u32>())?;
+        self.next += bytes.len();
+        Some(u32::from_ne_bytes(bytes.try_into().unwrap()))
+    }
+}
+
+impl Drop for ResultIter<'_> {
+    fn drop(&mut self) {
+        // The view has to go before the buffer can be unmapped
+        drop(self.view.take());
+        self.buffer.unmap();
+    }
+}
+
+static SHARED_GPU: OnceLock<GpuConsts> = OnceLock::new();
+
+/// One `GpuConsts` for the whole process, initialized from `vec_func.wgsl` by the first call,
+/// for scripts and tests that don't want to pass a handle around. Panics if there is no GPU;
+/// when initialization panicked, the next call tries again.
+pub fn shared_gpu() -> &'static GpuConsts {
+    SHARED_GPU.get_or_init(|| {
+        pollster::block_on(GpuConsts::initialaze(Op::Add.shader_file()))
+            .unwrap_or_else(|err| panic!("Error while initializing the shared GPU: {}", err))
+    })
+}
+
+/// `len` as the `u32` a kernel's `arrayLength` reports, `InputTooLong` if it doesn't fit.
+pub fn check_len(len: usize) -> Result<u32, GpuError> {
+    u32::try_from(len).map_err(|_| GpuError::InputTooLong { len })
+}
+
+/// Runs `entry` of `gpu` over `bindings` with `dims` workgroups and returns once it is done,
+/// without copying anything back, to time the kernel alone.
+pub fn dispatch_compute_only(
+    gpu: &GpuConsts,
+    bindings: &mut Bindings,
+    entry: &str,
+    binding_number: u32,
+    dims: (u32, u32, u32),
+) {
+    let stage =
+        Stage::new(entry, binding_number).dispatch(Dispatch::Workgroups(dims.0, dims.1, dims.2));
+    let bc = BufCoder::record(gpu, bindings, &[stage], false);
+
+    gpu.wait_for(&bc);
+    bc.discard();
+}
+
+/// Identifies a `Job` among the results of `run_concurrent`.
+pub type JobId = usize;
+
+/// Independent work for `run_concurrent`: `stages` over its own `bindings`.
+pub struct Job<'a> {
+    pub id: JobId,
+    pub bindings: Bindings,
+    pub stages: Vec<Stage<'a>>,
+}
+
+/// Records every job into its own command buffer and submits them all at once, so the GPU
+/// is free to overlap them. Returns binding 0 of every job with the time between its first
+/// and last timestamp, `None` without `TIMESTAMP_QUERY`; times of overlapping jobs overlap.
+pub fn run_concurrent(
+    gpu: &GpuConsts,
+    mut jobs: Vec<Job>,
+) -> Vec<(JobId, Vec<u32>, Option<Duration>)> {
+    let (commands, recorded): (Vec<_>, Vec<_>) = jobs
+        .iter_mut()
+        .map(|job| BufCoder::encode(gpu, &mut job.bindings, &job.stages, true))
+        .unzip();
+    let submission_index = gpu.queue.submit(commands);
+
+    jobs.iter()
+        .zip(recorded)
+        .map(|(job, recorded)| {
+            let bc = recorded.into_bufcoder(gpu, submission_index.clone());
+            let result = pollster::block_on(gpu.run::<u32>(&bc)).unwrap();
+            let time = pollster::block_on(gpu.compute_time(&bc));
+            (job.id, result, time)
+        })
+        .collect()
+}
+
+/// Number of 256-invocation workgroups that gives every one of `len` elements its own invocation.
+pub fn workgroup_count(len: usize) -> u32 {
+    workgroup_count_for(len, 256)
+}
+
+/// `workgroup_count` for kernels with `size` invocations per workgroup.
+pub fn workgroup_count_for(len: usize, size: u32) -> u32 {
+    len.div_ceil(size as usize).max(1) as u32
+}
+
+/// Sets the x size of every `@workgroup_size` attribute in `source` to `size`, so one
+/// shader can be compiled, with `GpuConsts::create_module`, at several workgroup sizes.
+/// Stands in for pipeline-overridable constants, which this wgpu can't set from the host.
+pub fn specialize_workgroup_size(source: &str, size: u32) -> String {
+    const ATTRIBUTE: &str = "@workgroup_size(";
+
+    let mut result = String::with_capacity(source.len());
+    let mut rest = source;
+    while let Some(start) = rest.find(ATTRIBUTE) {
+        let (head, tail) = rest.split_at(start + ATTRIBUTE.len());
+        result.push_str(head);
+        result.push_str(&size.to_string());
+
+        // Keeps the y and z sizes, only the first argument is replaced
+        let end = tail.find([',', ')']).unwrap_or(tail.len());
+        rest = &tail[end..];
+    }
+    result.push_str(rest);
+
+    result
+}
+
+/// Entry point of the shaders of `gen_unary_kernel`.
+pub const UNARY_ENTRY: &str = "unary_call";
+
+/// A complete shader replacing every `u32` `x` of binding 0 with `expr`, a WGSL expression
+/// of `x`, e.g. `gen_unary_kernel("x * x + 1u")`; `expr` isn't checked until the shader is
+/// compiled. Run it with `gpu_unary`, or pass it to `GpuConsts::create_module` and dispatch
+/// `UNARY_ENTRY` over one binding with `GpuConsts::dispatch_for`.
+pub fn gen_unary_kernel(expr: &str) -> String {
+    format!(
+        "@group(0) @binding(0) var<storage, read_write> data: array<u32>;
+
+@compute @workgroup_size(256)
+fn {}(
+  @builtin(global_invocation_id) global_id: vec3u,
+  @builtin(num_workgroups) num_workgroups: vec3u,
+) {{
+  let idx = global_id.x + (global_id.y + global_id.z * num_workgroups.y) * num_workgroups.x * 256u;
+  if idx < arrayLength(&data) {{
+    let x = data[idx];
+    data[idx] = {};
+  }}
+}}
+",
+        UNARY_ENTRY, expr
+    )
+}
+
+/// `data` mapped with the `gen_unary_kernel` of `expr` on `gpu`.
+pub fn gpu_unary(gpu: &GpuConsts, expr: &str, data: &[u32]) -> Vec<u32> {
+    if data.is_empty() {
+        return Vec::new();
+    }
+
+    let module = gpu.create_module("unary", &gen_unary_kernel(expr));
+    let mut bindings = Bindings::initialize_one(data.to_vec());
+    let stage = Stage::new(UNARY_ENTRY, 1)
+        .dispatch(gpu.dispatch_for(data.len()).unwrap())
+        .module(&module);
+    let bc = BufCoder::initialize_stages(gpu, &mut bindings, &[stage]);
+
+    pollster::block_on(gpu.run::<u32>(&bc)).unwrap()
+}
+
+/// The operations this demo implements both on the CPU and on the GPU.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Op {
+    /// Element-wise `data + data`, `vectorAddition_call` from `vec_func.wgsl`.
+    Add,
+    /// Sum of all elements, one atomic add per element, `vectorSum_call` from `sum_func.wgsl`.
+    Sum,
+    /// The same sum, halved pairwise within every workgroup first,
+    /// `optimized_vectorSum_call` from `optimized_sum_func.wgsl`.
+    OptimizedSum,
+}
+
+impl Op {
+    /// The shader a `GpuConsts` has to be initialized from to run this op.
+    pub fn shader_file(&self) -> &'static str {
+        match self {
+            Op::Add => "src/vec_func.wgsl",
+            Op::Sum => "src/sum_func.wgsl",
+            Op::OptimizedSum => "src/optimized_sum_func.wgsl",
+        }
+    }
+
+    pub fn entry_point(&self) -> &'static str {
+        match self {
+            Op::Add => "vectorAddition_call",
+            Op::Sum => "vectorSum_call",
+            Op::OptimizedSum => "optimized_vectorSum_call",
+        }
+    }
+
+    pub fn binding_number(&self) -> u32 {
+        match self {
+            Op::Add => 3,
+            Op::Sum | Op::OptimizedSum => 2,
+        }
+    }
+
+    /// Lays `data` out the way the op's kernel expects its bindings.
+    pub fn bindings(&self, data: &[u32]) -> Bindings {
+        match self {
+            Op::Add => {
+                Bindings::initialize_three(vec![0; data.len()], data.to_vec(), data.to_vec())
+            }
+            Op::Sum | Op::OptimizedSum => Bindings::initialize_two(vec![0; 1], data.to_vec()),
+        }
+    }
+
+    /// The CPU reference, shaped like the GPU output.
+    pub fn run_cpu(&self, data: &[u32]) -> Vec<u32> {
+        match self {
+            Op::Add => add_two_vec(data, data, data.len()),
+            Op::Sum => vec![sum_vec(data, data.len())],
+            // `optimized_sum_vec` takes an inclusive range, which an empty input doesn't have
+            Op::OptimizedSum if data.is_empty() => vec![0],
+            Op::OptimizedSum => vec![optimized_sum_vec(data, 0, data.len() - 1)],
+        }
+    }
+
+    /// Runs the op on `gpu`, which must be initialized from `shader_file`.
+    pub fn run_gpu(&self, gpu: &GpuConsts, data: &[u32]) -> Vec<u32> {
+        // Empty buffers can't be bound, and there is nothing to compute anyway
+        if data.is_empty() {
+            return self.run_cpu(data);
+        }
+
+        let mut bindings = self.bindings(data);
+        let stage = Stage::new(self.entry_point(), self.binding_number())
+            .dispatch(gpu.dispatch_for(data.len()).unwrap());
+        let bc = BufCoder::initialize_stages(gpu, &mut bindings, &[stage]);
+
+        pollster::block_on(gpu.run::<u32>(&bc)).unwrap()
+    }
+}
+
+/// Runs `op` on the GPU, or on the CPU when no GPU context can be created
+/// (no adapter, only a software one, missing shader, ...).
+pub fn run_op(op: Op, data: &[u32]) -> Vec<u32> {
+    let gpu = pollster::block_on(GpuConsts::initialaze(op.shader_file())).ok();
+
+    run_op_on(gpu.as_ref(), op, data)
+}
+
+/// Runs `op` on `gpu`, or on the CPU when there is no GPU.
+pub fn run_op_on(gpu: Option<&GpuConsts>, op: Op, data: &[u32]) -> Vec<u32> {
+    match gpu {
+        Some(gpu) => op.run_gpu(gpu, data),
+        None => op.run_cpu(data),
+    }
+}
+
+impl FromStr for Op {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "add" => Ok(Op::Add),
+            "sum" => Ok(Op::Sum),
+            "optimized_sum" => Ok(Op::OptimizedSum),
+            _ => Err(format!(
+                "unknown op `{}`, expected add, sum or optimized_sum",
+                name
+            )),
+        }
+    }
+}
+
+/// CPU and GPU timings of one op on one input.
+#[derive(Clone, Debug)]
+pub struct Comparison {
+    pub op: Op,
+    pub len: usize,
+    pub cpu_time: Duration,
+    /// Including upload and readback; `None` when there was no GPU to run on.
+    pub gpu_time: Option<Duration>,
+    /// Whether the GPU output equals the CPU one; `None` when there was no GPU to run on.
+    pub agree: Option<bool>,
+}
+
+/// Runs `op` over `data` on the CPU and, if there is one, on `gpu`.
+pub fn compare_op(gpu: Option<&GpuConsts>, op: Op, data: &[u32]) -> Comparison {
+    let start = Instant::now();
+    let cpu = op.run_cpu(data);
+    let cpu_time = start.elapsed();
+
+    let (gpu_time, agree) = match gpu {
+        Some(gpu) => {
+            let start = Instant::now();
+            let result = op.run_gpu(gpu, data);
+            (Some(start.elapsed()), Some(result == cpu))
+        }
+        None => (None, None),
+    };
+
+    Comparison {
+        op,
+        len: data.len(),
+        cpu_time,
+        gpu_time,
+        agree,
+    }
+}
+
+/// Spread of repeated timings of the same measurement.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TimingStats {
+    pub min: Duration,
+    pub median: Duration,
+    pub mean: Duration,
+    /// Population standard deviation.
+    pub std_dev: Duration,
+}
+
+impl TimingStats {
+    /// `None` for no samples; the median of an even count is the mean of the middle two.
+    pub fn from_samples(samples: &[Duration]) -> Option<TimingStats> {
+        if samples.is_empty() {
+            return None;
+        }
+
+        let mut sorted = samples.to_vec();
+        sorted.sort();
+        let middle = sorted.len() / 2;
+        let median = if sorted.len().is_multiple_of(2) {
+            (sorted[middle - 1] + sorted[middle]) / 2
+        } else {
+            sorted[middle]
+        };
+
+        let secs: Vec<f64> = sorted.iter().map(Duration::as_secs_f64).collect();
+        let mean = secs.iter().sum::<f64>() / secs.len() as f64;
+        let variance = secs.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / secs.len() as f64;
+
+        Some(TimingStats {
+            min: sorted[0],
+            median,
+            mean: Duration::from_secs_f64(mean),
+            std_dev: Duration::from_secs_f64(variance.sqrt()),
+        })
+    }
+}
+
+/// `compare_op` repeated, with the spread of the timings of every run.
+#[derive(Clone, Debug)]
+pub struct BenchResult {
+    pub op: Op,
+    pub len: usize,
+    pub cpu: TimingStats,
+    /// `None` when there was no GPU to run on.
+    pub gpu: Option<TimingStats>,
+    /// Whether the GPU output equaled the CPU one in every run.
+    pub agree: Option<bool>,
+}
+
+/// Runs `compare_op` `runs` times over the same `data`.
+pub fn bench_op(gpu: Option<&GpuConsts>, op: Op, data: &[u32], runs: usize) -> BenchResult {
+    assert!(runs > 0, "at least one run is needed");
+
+    let comparisons: Vec<Comparison> = (0..runs).map(|_| compare_op(gpu, op, data)).collect();
+    let cpu_times: Vec<Duration> = comparisons.iter().map(|c| c.cpu_time).collect();
+    let gpu_times: Vec<Duration> = comparisons.iter().filter_map(|c| c.gpu_time).collect();
+
+    BenchResult {
+        op,
+        len: data.len(),
+        cpu: TimingStats::from_samples(&cpu_times).unwrap(),
+        gpu: TimingStats::from_samples(&gpu_times),
+        agree: gpu.map(|_| comparisons.iter().all(|c| c.agree == Some(true))),
+    }
+}
+
+/// Largest input `crossover_size` tries, on devices whose limits allow it.
+pub const CROSSOVER_MAX_LEN: usize = 1 << 24;
+
+/// Smallest input length at which `op` takes less time on `gpu`, uploads and readback
+/// included, than on the CPU; `None` if the CPU is still faster at the largest length tried,
+/// `CROSSOVER_MAX_LEN` or less when a binding of that many elements doesn't fit `gpu`.
+/// Binary-searches the length, so it assumes the GPU stays faster above the crossover.
+/// Panics if the GPU result ever differs from the CPU one, a wrong result is no speedup.
+pub fn crossover_size(gpu: &GpuConsts, op: Op) -> Option<usize> {
+    gpu.warmup(op.entry_point());
+    let max_binding_len = gpu.device.limits().max_storage_buffer_binding_size as usize / 4;
+    let max_len = CROSSOVER_MAX_LEN.min(max_binding_len);
+
+    // The best of a few runs, single runs are too noisy at small sizes
+    let gpu_faster = |len: usize| {
+        let data = generate_data(len, 0);
+        let runs: Vec<Comparison> = (0..3).map(|_| compare_op(Some(gpu), op, &data)).collect();
+        assert!(
+            runs.iter().all(|run| run.agree == Some(true)),
+            "{:?} on the GPU differs from the CPU over {} elements",
+            op,
+            len
+        );
+        let cpu = runs.iter().map(|run| run.cpu_time).min().unwrap();
+        let gpu = runs.iter().filter_map(|run| run.gpu_time).min().unwrap();
+        gpu < cpu
+    };
+
+    search_crossover(max_len, gpu_faster)
+}
+
+// The binary search of `crossover_size` over `1..=max_len`, with the timing behind
+// `gpu_faster` so tests can put a known crossover there.
+fn search_crossover(max_len: usize, mut gpu_faster: impl FnMut(usize) -> bool) -> Option<usize> {
+    if !gpu_faster(max_len) {
+        return None;
+    }
+
+    let (mut low, mut high) = (1, max_len);
+    while low < high {
+        let mid = low + (high - low) / 2;
+        if gpu_faster(mid) {
+            high = mid;
+        } else {
+            low = mid + 1;
+        }
+    }
+
+    Some(low)
+}
+
+/// `optimized_sum_vec` against `gpu_reduce_sum` over one input, see `compare_reductions`.
+#[derive(Clone, Debug)]
+pub struct ReductionComparison {
+    pub len: usize,
+    pub cpu_time: Duration,
+    /// Including upload and readback.
+    pub gpu_time: Duration,
+    pub cpu_sum: u64,
+    pub gpu_sum: u64,
+}
+
+impl ReductionComparison {
+    pub fn agree(&self) -> bool {
+        self.cpu_sum == self.gpu_sum
+    }
+
+    pub fn gpu_wins(&self) -> bool {
+        self.gpu_time < self.cpu_time
+    }
+}
+
+/// Times the recursive CPU reduction and the GPU tree reduction over `generate_data` inputs
+/// of each of `sizes`. The values are at most 100, so below about 42M elements neither
+/// `u32` sum wraps and both equal the `u64` sum.
+pub fn compare_reductions(gpu: &GpuConsts, sizes: &[usize]) -> Vec<ReductionComparison> {
+    sizes
+        .iter()
+        .map(|&len| {
+            let data = generate_data(len, 0);
+
+            let start = Instant::now();
+            let cpu_sum = if data.is_empty() {
+                0
+            } else {
+                optimized_sum_vec(&data, 0, data.len() - 1)
+            };
+            let cpu_time = start.elapsed();
+
+            let start = Instant::now();
+            let gpu_sum = gpu_reduce_sum(gpu, &data);
+            let gpu_time = start.elapsed();
+
+            ReductionComparison {
+                len,
+                cpu_time,
+                gpu_time,
+                cpu_sum: cpu_sum as u64,
+                gpu_sum: gpu_sum as u64,
+            }
+        })
+        .collect()
+}
+
+/// Absolute errors of the `f32` sums of one input against `f64_sum_f32`, see
+/// `compare_precision`.
+#[derive(Clone, Copy, Debug)]
+pub struct PrecisionComparison {
+    pub truth: f64,
+    pub naive_error: f64,
+    pub kahan_error: f64,
+    /// Of `gpu_atomic_sum_f32`, `None` without a GPU.
+    pub gpu_error: Option<f64>,
+}
+
+/// Sums `data` naively and with Kahan summation in `f32` on the CPU and with
+/// `gpu_atomic_sum_f32` on `gpu`, and measures each against the `f64` sum. On inputs mixing
+/// large and small magnitudes the naive sum drifts furthest, e.g. `1e8` followed by ones.
+pub fn compare_precision(gpu: Option<&GpuConsts>, data: &[f32]) -> PrecisionComparison {
+    let truth = f64_sum_f32(data);
+    let error = |sum: f32| (sum as f64 - truth).abs();
+
+    PrecisionComparison {
+        truth,
+        naive_error: error(naive_sum_f32(data)),
+        kahan_error: error(kahan_sum_f32(data)),
+        gpu_error: gpu.map(|gpu| error(gpu_atomic_sum_f32(gpu, data))),
+    }
+}
+
+/// Runs `op` over `data` on the CPU and on `gpu` and panics, naming the first differing
+/// index, both values there and the start of the input, unless the outputs are equal.
+#[cfg(feature = "test-util")]
+pub fn assert_cpu_gpu_agree(gpu: &GpuConsts, op: Op, data: &[u32]) {
+    let cpu = op.run_cpu(data);
+    let gpu = op.run_gpu(gpu, data);
+    if cpu == gpu {
+        return;
+    }
+
+    let sample = &data[..data.len().min(8)];
+    match cpu.iter().zip(&gpu).position(|(c, g)| c != g) {
+        Some(index) => panic!(
+            "{:?} differs at index {}: cpu {}, gpu {} (input of {} starts with {:?})",
+            op,
+            index,
+            cpu[index],
+            gpu[index],
+            data.len(),
+            sample
+        ),
+        None => panic!(
+            "{:?} output lengths differ: cpu {}, gpu {} (input of {} starts with {:?})",
+            op,
+            cpu.len(),
+            gpu.len(),
+            data.len(),
+            sample
+        ),
+    }
+}
+
+/// Timings of two entry points run on the same data, and whether their outputs agree.
+#[derive(Clone, Debug)]
+pub struct KernelAbResult {
+    pub a_time: Duration,
+    pub b_time: Duration,
+    pub agree: bool,
+}
+
+/// Runs the entry points `a_entry` and `b_entry` of `gpu` on identical copies of `data`.
+/// Both must use the layout and the `flat_index` of `sum_func.wgsl`: output at binding 0,
+/// `data` at binding 1.
+pub fn compare_kernels(
+    gpu: &GpuConsts,
+    a_entry: &str,
+    b_entry: &str,
+    data: &[u32],
+) -> KernelAbResult {
+    let time_kernel = |entry: &str| {
+        gpu.warmup(entry);
+
+        let start = Instant::now();
+        let mut bindings = Bindings::initialize_two(vec![0; data.len()], data.to_vec());
+        let stage = Stage::new(entry, 2).dispatch(gpu.dispatch_for(data.len()).unwrap());
+        let bc = BufCoder::initialize_stages(gpu, &mut bindings, &[stage]);
+        let result = pollster::block_on(gpu.run::<u32>(&bc)).unwrap();
+
+        (start.elapsed(), result)
+    };
+
+    let (a_time, a_result) = time_kernel(a_entry);
+    let (b_time, b_result) = time_kernel(b_entry);
+
+    KernelAbResult {
+        a_time,
+        b_time,
+        agree: a_result == b_result,
+    }
+}
+
+/// Runs `op` on `gpu` `runs` times over the same `data` and tells whether every output
+/// is bit-identical to the first one, which e.g. unordered float atomics break.
+pub fn check_determinism(gpu: &GpuConsts, op: Op, data: &[u32], runs: usize) -> bool {
+    let first = op.run_gpu(gpu, data);
+
+    (1..runs).all(|_| op.run_gpu(gpu, data) == first)
+}
+
+/// Sums the first `gpu_fraction` of `data` on `gpu` and the rest on the CPU at the same time.
+/// The GPU part uses its own shader, so `gpu` can be initialized from any file.
+pub fn hybrid_sum(gpu: &GpuConsts, data: &[u32], gpu_fraction: f32) -> u64 {
+    let split = ((data.len() as f32 * gpu_fraction.clamp(0.0, 1.0)) as usize).min(data.len());
+    let (gpu_part, cpu_part) = data.split_at(split);
+
+    // Submitting doesn't wait for the GPU, so it works while the CPU sums its part below
+    let pending = (!gpu_part.is_empty()).then(|| {
+        let module = gpu.create_module("wide sum", include_str!("wide_sum_func.wgsl"));
+        let mut bindings = Bindings::initialize_two(vec![0; 2], gpu_part.to_vec());
+        let stage = Stage::new("wideVectorSum_call", 2)
+            .dispatch(gpu.dispatch_for(gpu_part.len()).unwrap())
+            .module(&module);
+        BufCoder::initialize_stages(gpu, &mut bindings, &[stage])
+    });
+
+    let cpu_sum: u64 = cpu_part.iter().map(|&x| x as u64).sum();
+
+    let gpu_sum = pending.map_or(0, |bc| {
+        // Low word plus the number of times it wrapped around
+        pollster::block_on(gpu.run_with(&bc, |words| words[0] as u64 | (words[1] as u64) << 32))
+    });
+
+    cpu_sum + gpu_sum
+}
+
+// Runs `entry` of `atomic_func.wgsl` over `data` with `output` as the initial binding 0.
+fn run_atomic(gpu: &GpuConsts, entry: &str, output: Vec<u32>, data: &[u32]) -> Vec<u32> {
+    let module = gpu.create_module("atomic", include_str!("atomic_func.wgsl"));
+    let mut bindings = Bindings::initialize_two(output, data.to_vec());
+    let stage = Stage::new(entry, 2)
+        .dispatch(gpu.dispatch_for(data.len()).unwrap())
+        .module(&module);
+    let bc = BufCoder::initialize_stages(gpu, &mut bindings, &[stage]);
+
+    pollster::block_on(gpu.run::<u32>(&bc)).unwrap()
+}
+
+/// Largest element of `data` with `atomicMax` on `gpu`, `None` when `data` is empty.
+pub fn gpu_atomic_max(gpu: &GpuConsts, data: &[u32]) -> Option<u32> {
+    if data.is_empty() {
+        return None;
+    }
+
+    Some(run_atomic(gpu, "atomicMax_call", vec![0], data)[0])
+}
+
+/// Smallest element of `data` with `atomicMin` on `gpu`, `None` when `data` is empty.
+pub fn gpu_atomic_min(gpu: &GpuConsts, data: &[u32]) -> Option<u32> {
+    if data.is_empty() {
+        return None;
+    }
+
+    Some(run_atomic(gpu, "atomicMin_call", vec![u32::MAX], data)[0])
+}
+
+/// Largest element of `data` and its index on `gpu`, the lowest index among equal maxima,
+/// `None` when `data` is empty. See `cpu_argmax`.
+pub fn gpu_argmax(gpu: &GpuConsts, data: &[u32]) -> Option<(u32, u32)> {
+    if data.is_empty() {
+        return None;
+    }
+
+    let module = gpu.create_module("atomic", include_str!("atomic_func.wgsl"));
+    let mut bindings = Bindings::initialize_two(vec![0, u32::MAX], data.to_vec());
+    // The index stage starts once every max of the value stage has landed
+    let dispatch = gpu.dispatch_for(data.len()).unwrap();
+    let stages = [
+        Stage::new("argmaxValue_call", 2)
+            .dispatch(dispatch)
+            .module(&module),
+        Stage::new("argmaxIndex_call", 2)
+            .dispatch(dispatch)
+            .module(&module),
+    ];
+    let bc = BufCoder::initialize_stages(gpu, &mut bindings, &stages);
+
+    let output = pollster::block_on(gpu.run::<u32>(&bc)).unwrap();
+    Some((output[0], output[1]))
+}
+
+/// `u64` sum of `data`, as a low word and the count of its wraps, see `wide_sum_func.wgsl`.
+/// This wgpu has no `SHADER_INT64` feature to request, so WGSL can't use `u64` atomics yet.
+pub fn gpu_wide_sum(gpu: &GpuConsts, data: &[u32]) -> u64 {
+    if data.is_empty() {
+        return 0;
+    }
+
+    let module = gpu.create_module("wide sum", include_str!("wide_sum_func.wgsl"));
+    let mut bindings = Bindings::initialize_two(vec![0; 2], data.to_vec());
+    let stage = Stage::new("wideVectorSum_call", 2)
+        .dispatch(gpu.dispatch_for(data.len()).unwrap())
+        .module(&module);
+    let bc = BufCoder::initialize_stages(gpu, &mut bindings, &[stage]);
+
+    let words = pollster::block_on(gpu.run::<u32>(&bc)).unwrap();
+    words[0] as u64 | (words[1] as u64) << 32
+}
+
+/// Wrapping `u32` sum of `data`, halving the values of each workgroup in shared memory.
+/// This wgpu has no subgroup feature to request, so WGSL can't use `subgroupAdd` yet.
+pub fn gpu_reduce_sum(gpu: &GpuConsts, data: &[u32]) -> u32 {
+    if data.is_empty() {
+        return 0;
+    }
+
+    let module = gpu.create_module("tree sum", include_str!("tree_sum_func.wgsl"));
+    let mut bindings = Bindings::initialize_two(vec![0], data.to_vec());
+    let stage = Stage::new("treeSum_call", 2)
+        .dispatch(gpu.dispatch_for(data.len()).unwrap())
+        .module(&module);
+    let bc = BufCoder::initialize_stages(gpu, &mut bindings, &[stage]);
+
+    pollster::block_on(gpu.run::<u32>(&bc)).unwrap()[0]
+}
+
+/// `data[i * stride] + 1` for every `i` below `count`, to show what uncoalesced reads cost
+/// compared to `stride` 1. `StrideOutOfBounds` unless `stride * count <= data.len()`.
+pub fn gpu_strided_map(
+    gpu: &GpuConsts,
+    data: &[u32],
+    stride: usize,
+    count: usize,
+) -> Result<Vec<u32>, GpuError> {
+    if stride == 0
+        || stride
+            .checked_mul(count)
+            .filter(|&end| end <= data.len())
+            .is_none()
+    {
+        return Err(GpuError::StrideOutOfBounds {
+            stride,
+            count,
+            len: data.len(),
+        });
+    }
+    if count == 0 {
+        return Ok(Vec::new());
+    }
+
+    let module = gpu.create_module("strided", include_str!("strided_func.wgsl"));
+    let mut bindings = Bindings {
+        generated_len: Some(count),
+        ..Bindings::initialize_two(Vec::new(), data.to_vec())
+    };
+    let stage = Stage::new("stridedIncrement_call", 2)
+        .dispatch(gpu.dispatch_for(count)?)
+        .module(&module)
+        .params([check_len(count)?, check_len(stride)?, 0, 0]);
+    let bc = BufCoder::try_initialize_stages(gpu, &mut bindings, &[stage])?;
+
+    pollster::block_on(gpu.run::<u32>(&bc))
+}
+
+/// `values[indices[i]]` for every index, with `u32` indices and `f32` values in bindings
+/// of different types, see `gather_func.wgsl`. `None` when an index is out of range.
+pub fn gpu_gather_f32(gpu: &GpuConsts, indices: &[u32], values: &[f32]) -> Option<Vec<f32>> {
+    if indices.iter().any(|&index| index as usize >= values.len()) {
+        return None;
+    }
+    if indices.is_empty() {
+        return Some(Vec::new());
+    }
+
+    let module = gpu.create_module("gather", include_str!("gather_func.wgsl"));
+    let mut bindings = Bindings::initialize_generator(indices.len());
+    bindings.set_binding(1, indices);
+    bindings.set_binding(2, values);
+    let stage = Stage::new("gatherF32_call", 3)
+        .dispatch(gpu.dispatch_for(indices.len()).ok()?)
+        .module(&module);
+    let bc = BufCoder::initialize_stages(gpu, &mut bindings, &[stage]);
+
+    pollster::block_on(gpu.run::<f32>(&bc)).ok()
+}
+
+/// `a * x + y` element-wise on `gpu`, `None` unless `x` and `y` have the same length.
+/// The GPU fuses the multiply and add, compare with `saxpy` within a `Tolerance`.
+pub fn gpu_saxpy(gpu: &GpuConsts, a: f32, x: &[f32], y: &[f32]) -> Option<Vec<f32>> {
+    if x.len() != y.len() {
+        return None;
+    }
+    if x.is_empty() {
+        return Some(Vec::new());
+    }
+
+    let module = gpu.create_module("saxpy", include_str!("saxpy_func.wgsl"));
+    let mut bindings = Bindings::initialize_one(Vec::new());
+    bindings.set_binding(0, y);
+    bindings.set_binding(1, x);
+    let stage = Stage::new("saxpy_call", 2)
+        .dispatch(gpu.dispatch_for(x.len()).ok()?)
+        .module(&module)
+        .params([a.to_bits(), 0, 0, 0]);
+    let bc = BufCoder::initialize_stages(gpu, &mut bindings, &[stage]);
+
+    pollster::block_on(gpu.run::<f32>(&bc)).ok()
+}
+
+/// Largest `window` of `gpu_window_sum`, the elements it looks back at have to fit in
+/// shared memory next to the ones of the workgroup.
+pub const WINDOW_SUM_MAX: usize = 257;
+
+/// Trailing sliding-window sum, `output[i]` is the wrapping sum of the `window` elements
+/// ending at `data[i]`. The first `window - 1` windows are partial, see `window_sum_vec`:
+/// a `window` of 0 sums nothing, and one longer than `data` is partial everywhere, the
+/// same as a window of `data.len()`. `LimitExceeded` when that is still over `WINDOW_SUM_MAX`.
+pub fn gpu_window_sum(gpu: &GpuConsts, data: &[u32], window: usize) -> Result<Vec<u32>, GpuError> {
+    let len = check_len(data.len())?;
+    if window == 0 {
+        return Ok(vec![0; data.len()]);
+    }
+    let window = window.min(data.len());
+    if window > WINDOW_SUM_MAX {
+        return Err(GpuError::LimitExceeded {
+            limit: "window",
+            requested: window as u64,
+            max: WINDOW_SUM_MAX as u64,
+        });
+    }
+    if data.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    let module = gpu.create_module("window sum", include_str!("window_func.wgsl"));
+    let mut bindings = Bindings {
+        generated_len: Some(data.len()),
+        ..Bindings::initialize_two(Vec::new(), data.to_vec())
+    };
+    let stage = Stage::new("windowSum_call", 2)
+        .dispatch(gpu.dispatch_for(data.len())?)
+        .module(&module)
+        .params([len, window as u32, 0, 0]);
+    let bc = BufCoder::initialize_stages(gpu, &mut bindings, &[stage]);
+
+    pollster::block_on(gpu.run::<u32>(&bc))
+}
+
+/// Wrapping `u32` sum of `data` with a grid-stride loop: only enough invocations for about
+/// `work_per_thread` elements each are launched, and each one strides over the whole input.
+/// `InputTooLong` when the length of `data` doesn't fit in a `u32`.
+pub fn gpu_grid_stride_sum(
+    gpu: &GpuConsts,
+    data: &[u32],
+    work_per_thread: usize,
+) -> Result<u32, GpuError> {
+    assert!(work_per_thread > 0, "every invocation needs some work");
+    let len = check_len(data.len())?;
+    if data.is_empty() {
+        return Ok(0);
+    }
+
+    let plan = gpu.plan_dispatch(workgroup_count(data.len().div_ceil(work_per_thread)) as u64)?;
+    let (x, y, z) = plan.workgroups;
+    // More invocations than u32 can count still reach every element at most once
+    let stride = (x * y * z).saturating_mul(256);
+    let module = gpu.create_module("grid stride", include_str!("grid_stride_func.wgsl"));
+    let mut bindings = Bindings::initialize_two(vec![0], data.to_vec());
+    let stage = Stage::new("gridStrideSum_call", 2)
+        .dispatch(plan.dispatch())
+        .module(&module)
+        .params([len, stride, 0, 0]);
+    let bc = BufCoder::initialize_stages(gpu, &mut bindings, &[stage]);
+
+    Ok(pollster::block_on(gpu.run::<u32>(&bc))?[0])
+}
+
+/// Wrapping `u32` sum of every vector of `batch`, all packed into one buffer and summed
+/// by one workgroup each in a single dispatch. `InputTooLong` when the packed vectors
+/// don't fit in a `u32` offset, `LimitExceeded` when not even a 3D grid has a workgroup
+/// for every vector.
+pub fn gpu_batched_sum(gpu: &GpuConsts, batch: &[Vec<u32>]) -> Result<Vec<u32>, GpuError> {
+    if batch.is_empty() {
+        return Ok(Vec::new());
+    }
+
+    // Vector `i` is `data[offsets[i]..offsets[i + 1]]`
+    let mut data = Vec::with_capacity(batch.iter().map(Vec::len).sum());
+    let mut offsets = Vec::with_capacity(batch.len() + 1);
+    offsets.push(0);
+    for vector in batch {
+        data.extend_from_slice(vector);
+        offsets.push(check_len(data.len())?);
+    }
+    // Empty buffers can't be bound, the padding is outside of every vector
+    if data.is_empty() {
+        data.push(0);
+    }
+
+    let module = gpu.create_module("batched sum", include_str!("batched_sum_func.wgsl"));
+    let mut bindings = Bindings::initialize_three(vec![0; batch.len()], data, offsets);
+    let stage = Stage::new("batchedSum_call", 3)
+        .dispatch(gpu.plan_dispatch(batch.len() as u64)?.dispatch())
+        .module(&module);
+    let bc = BufCoder::initialize_stages(gpu, &mut bindings, &[stage]);
+
+    pollster::block_on(gpu.run::<u32>(&bc))
+}
+
+/// `[0, 1, 4, 9, ...]` up to `(len - 1)^2`, wrapping, made by a generator kernel
+/// without any input.
+pub fn gpu_squares(gpu: &GpuConsts, len: usize) -> Vec<u32> {
+    if len == 0 {
+        return Vec::new();
+    }
+
+    let module = gpu.create_module("generator", include_str!("generator_func.wgsl"));
+    let mut bindings = Bindings::initialize_generator(len);
+    let stage = Stage::new("squares_call", 1)
+        .dispatch(gpu.dispatch_for(len).unwrap())
+        .module(&module);
+    let bc = BufCoder::initialize_stages(gpu, &mut bindings, &[stage]);
+
+    pollster::block_on(gpu.run::<u32>(&bc)).unwrap()
+}
+
+/// `a + b` computed in half precision, two `f16` packed in every `u32` of the buffers so
+/// only half of the bytes of `f32` are moved, see `f16_func.wgsl`. The inputs are rounded
+/// to `f16` first, compare with `add_two_vec_f32` within `Tolerance::F16`.
+/// `pack2x16float`/`unpack2x16float` work without `Features::SHADER_F16`; native `f16`
+/// arithmetic would need it, but the WGSL front end of naga 0.13 can't parse `enable f16`.
+#[cfg(feature = "f16")]
+pub fn gpu_add_two_vec_f16(gpu: &GpuConsts, a: &[f32], b: &[f32]) -> Vec<f32> {
+    let len = a.len().min(b.len());
+    if len == 0 {
+        return Vec::new();
+    }
+
+    // An odd length gets a zero in the upper half of the last word
+    let pack = |values: &[f32]| -> Vec<u32> {
+        let mut halves: Vec<half::f16> = values[..len]
+            .iter()
+            .map(|&value| half::f16::from_f32(value))
+            .collect();
+        if len % 2 == 1 {
+            halves.push(half::f16::ZERO);
+        }
+        bytemuck::cast_slice(&halves).to_vec()
+    };
+
+    let module = gpu.create_module("f16", include_str!("f16_func.wgsl"));
+    let words = len.div_ceil(2);
+    let mut bindings = Bindings::initialize_two(pack(a), pack(b));
+    let stage = Stage::new("vectorAdditionF16_call", 2)
+        .dispatch(gpu.dispatch_for(words).unwrap())
+        .module(&module);
+    let bc = BufCoder::initialize_stages(gpu, &mut bindings, &[stage]);
+
+    let halves = pollster::block_on(gpu.run::<half::f16>(&bc)).unwrap();
+    halves[..len].iter().map(|value| value.to_f32()).collect()
+}
+
+/// `f32` sum of `data` from a compare-exchange loop on the bits of the sum, as WGSL has no
+/// atomic float add. Not deterministic: the order of the additions varies between runs and
+/// so does the rounding, compare with `kahan_sum_f32` within a tolerance. Every invocation
+/// contends for the same word, so this shows the technique rather than a fast reduction.
+pub fn gpu_atomic_sum_f32(gpu: &GpuConsts, data: &[f32]) -> f32 {
+    if data.is_empty() {
+        return 0.0;
+    }
+
+    // The bits of 0.0 are all zero
+    let bits = run_atomic(
+        gpu,
+        "atomicSumF32_call",
+        vec![0],
+        bytemuck::cast_slice(data),
+    );
+    f32::from_bits(bits[0])
+}
+
+/// Number of elements of `data` a counter kernel of `atomic_func.wgsl` accepts,
+/// `countEven_call` or `countOdd_call`.
+pub fn gpu_count(gpu: &GpuConsts, data: &[u32], predicate_entry: &str) -> u32 {
+    if data.is_empty() {
+        return 0;
+    }
+
+    run_atomic(gpu, predicate_entry, vec![0], data)[0]
+}
+
+/// Whether a mask kernel of `atomic_func.wgsl`, `maskEven_call` or `maskOdd_call`, accepts
+/// each element of `data`. The GPU writes one bit per element, 32 times less than a `u32`.
+pub fn gpu_mask(gpu: &GpuConsts, data: &[u32], predicate_entry: &str) -> Vec<bool> {
+    if data.is_empty() {
+        return Vec::new();
+    }
+
+    let words = run_atomic(gpu, predicate_entry, vec![0; data.len().div_ceil(32)], data);
+    // The bits of the last word past `data.len()` are never set and not returned
+    (0..data.len())
+        .map(|idx| words[idx / 32] & (1 << (idx % 32)) != 0)
+        .collect()
+}
+
+/// The CPU reference of `gpu_mask`.
+pub fn cpu_mask(data: &[u32], predicate: impl Fn(u32) -> bool) -> Vec<bool> {
+    data.iter().map(|&x| predicate(x)).collect()
+}
+
+/// The CPU reference of `gpu_count`.
+pub fn cpu_count(data: &[u32], predicate: impl Fn(u32) -> bool) -> u32 {
+    data.iter().filter(|&&x| predicate(x)).count() as u32
+}
+
+/// Wrapping element-wise `a + b` on `gpu` with `vectorAdditionChecked_call`, and whether
+/// any element overflowed; `checked_add_two_vec` returns `None` for exactly those inputs.
+pub fn gpu_checked_add(gpu: &GpuConsts, a: &[u32], b: &[u32]) -> (Vec<u32>, bool) {
+    let len = a.len().min(b.len());
+    if len == 0 {
+        return (Vec::new(), false);
+    }
+
+    let module = gpu.create_module("checked add", include_str!("vec_func.wgsl"));
+    let mut bindings =
+        Bindings::initialize_four(vec![0; len], a[..len].to_vec(), b[..len].to_vec(), vec![0]);
+    let stage = Stage::new("vectorAdditionChecked_call", 4)
+        .dispatch(gpu.dispatch_for(len).unwrap())
+        .module(&module);
+    let bc = BufCoder::initialize_stages(gpu, &mut bindings, &[stage]);
+
+    let result = pollster::block_on(gpu.run::<u32>(&bc)).unwrap();
+    let overflow = pollster::block_on(gpu.read_binding(&bc, 3)).unwrap();
+
+    (result, overflow[0] != 0)
+}
+
+/// Wrapping element-wise `a + b` of `i32`s on `gpu`, see `add_two_vec_i32`.
+pub fn gpu_add_two_vec_i32(gpu: &GpuConsts, a: &[i32], b: &[i32]) -> Vec<i32> {
+    let len = a.len().min(b.len());
+    if len == 0 {
+        return Vec::new();
+    }
+
+    let module = gpu.create_module("signed", include_str!("signed_func.wgsl"));
+    let mut bindings =
+        Bindings::initialize_three_i32(vec![0; len], a[..len].to_vec(), b[..len].to_vec());
+    let stage = Stage::new("vectorAdditionI32_call", 3)
+        .dispatch(gpu.dispatch_for(len).unwrap())
+        .module(&module);
+    let bc = BufCoder::initialize_stages(gpu, &mut bindings, &[stage]);
+
+    pollster::block_on(gpu.run::<i32>(&bc)).unwrap()
+}
+
+/// `i32` sum of `data` on `gpu`, accumulated in order by one invocation. Equals
+/// `sum_vec_i32` as long as every partial sum fits into an `i32`, and wraps like it otherwise.
+pub fn gpu_sum_vec_i32(gpu: &GpuConsts, data: &[i32]) -> i32 {
+    if data.is_empty() {
+        return 0;
+    }
+
+    let module = gpu.create_module("signed", include_str!("signed_func.wgsl"));
+    let mut bindings = Bindings::initialize_two_i32(vec![0], data.to_vec());
+    let stage = Stage::new("vectorSumI32_call", 2)
+        .dispatch(Dispatch::Workgroups(1, 1, 1))
+        .module(&module);
+    let bc = BufCoder::initialize_stages(gpu, &mut bindings, &[stage]);
+
+    pollster::block_on(gpu.run::<i32>(&bc)).unwrap()[0]
+}
+
+/// Sum, min, max and number of elements of some data.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Stats {
+    pub sum: u64,
+    pub min: u32,
+    pub max: u32,
+    pub count: usize,
+}
+
+/// `Stats` of `data` from one fused atomic kernel on `gpu`, `None` when `data` is empty.
+pub fn gpu_stats(gpu: &GpuConsts, data: &[u32]) -> Option<Stats> {
+    if data.is_empty() {
+        return None;
+    }
+
+    let words = run_atomic(gpu, "atomicStats_call", vec![0, 0, u32::MAX, 0, 0], data);
+
+    Some(Stats {
+        sum: words[0] as u64 | (words[1] as u64) << 32,
+        min: words[2],
+        max: words[3],
+        count: words[4] as usize,
+    })
+}
+
+/// The CPU reference of `gpu_stats`.
+pub fn cpu_stats(data: &[u32]) -> Option<Stats> {
+    Some(Stats {
+        sum: data.iter().map(|&x| x as u64).sum(),
+        min: *data.iter().min()?,
+        max: *data.iter().max()?,
+        count: data.len(),
+    })
+}
+
+/// Upload and download bandwidth in GB/s of a round trip of about `bytes` through a GPU buffer,
+/// without any compute. Both directions wait until the copy has finished on the device.
+pub fn measure_transfer_bandwidth(gpu: &GpuConsts, bytes: usize) -> (f64, f64) {
+    let data = vec![1u32; bytes.div_ceil(std::mem::size_of::<u32>()).max(1)];
+    let size = std::mem::size_of_val(data.as_slice()) as BufferAddress;
+
+    let buffer = gpu.device.create_buffer(&wgpu::BufferDescriptor {
+        label: gpu.label("bandwidth", Some("Bandwidth Buffer")).as_deref(),
+        size,
+        usage: wgpu::BufferUsages::STORAGE
+            | wgpu::BufferUsages::COPY_DST
+            | wgpu::BufferUsages::COPY_SRC,
+        mapped_at_creation: false,
+    });
+    let staging_buffer = gpu.device.create_buffer(&wgpu::BufferDescriptor {
+        label: gpu
+            .label("bandwidth-staging", Some("Staging Buffer"))
+            .as_deref(),
+        size,
+        usage: wgpu::BufferUsages::MAP_READ | wgpu::BufferUsages::COPY_DST,
+        mapped_at_creation: false,
+    });
+
+    // Host to device: the write is flushed by the next submission
+    let start = Instant::now();
+    gpu.queue
+        .write_buffer(&buffer, 0, bytemuck::cast_slice(&data));
+    gpu.queue.submit(std::iter::empty());
+    gpu.wait_idle();
+    let upload = start.elapsed();
+
+    // Device to host: copy into the staging buffer and map it
+    let start = Instant::now();
+    let mut encoder = gpu
+        .device
+        .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+    encoder.copy_buffer_to_buffer(&buffer, 0, &staging_buffer, 0, size);
+    gpu.queue.submit(Some(encoder.finish()));
+    pollster::block_on(gpu.map_staging(&staging_buffer, |data| data.len()));
+    let download = start.elapsed();
+
+    let gb = size as f64 / 1e9;
+    (gb / upload.as_secs_f64(), gb / download.as_secs_f64())
+}
+
+/// How `measure_clear` zeroes a buffer; which one is fastest depends on the backend.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ClearStrategy {
+    /// `CommandEncoder::clear_buffer`, a transfer command.
+    ClearBuffer,
+    /// The `clear_call` kernel of `clear_func.wgsl` storing zeros.
+    Kernel,
+    /// A new buffer from `create_buffer_init`, with the zeros uploaded from the host.
+    InitZeros,
+}
+
+/// Zeroes a buffer of `len` elements with `strategy` and returns its contents read back,
+/// with the time until the GPU finished. `ClearBuffer` and `Kernel` clear a buffer of
+/// `u32::MAX` made beforehand, outside of the measured time.
+pub fn measure_clear(gpu: &GpuConsts, len: usize, strategy: ClearStrategy) -> (Vec<u32>, Duration) {
+    let len = len.max(1);
+    let usage =
+        wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::COPY_SRC;
+    let filled = |value: u32| {
+        gpu.device
+            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: gpu.label("clear", Some("Clear Buffer")).as_deref(),
+                contents: bytemuck::cast_slice(&vec![value; len]),
+                usage,
+            })
+    };
+
+    let (buffer, time) = match strategy {
+        ClearStrategy::ClearBuffer => {
+            let buffer = filled(u32::MAX);
+            let start = Instant::now();
+            let mut encoder = gpu
+                .device
+                .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+            encoder.clear_buffer(&buffer, 0, None);
+            gpu.queue.submit(Some(encoder.finish()));
+            gpu.wait_idle();
+            (buffer, start.elapsed())
+        }
+        ClearStrategy::Kernel => {
+            let buffer = filled(u32::MAX);
+            let module = gpu.create_module("clear", include_str!("clear_func.wgsl"));
+            let pipeline = gpu
+                .device
+                .create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                    label: gpu.label("clear-pipeline", Some("clear")).as_deref(),
+                    layout: None,
+                    module: &module,
+                    entry_point: "clear_call",
+                });
+            let bind_group = gpu.device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: gpu.label("clear-bind-group", None).as_deref(),
+                layout: &pipeline.get_bind_group_layout(0),
+                entries: &[wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: buffer.as_entire_binding(),
+                }],
+            });
+
+            let start = Instant::now();
+            let mut encoder = gpu
+                .device
+                .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+            {
+                let mut cpass =
+                    encoder.begin_compute_pass(&wgpu::ComputePassDescriptor { label: None });
+                cpass.set_pipeline(&pipeline);
+                cpass.set_bind_group(0, &bind_group, &[]);
+                let (x, y, z) = gpu
+                    .plan_dispatch(workgroup_count(len) as u64)
+                    .unwrap()
+                    .workgroups;
+                cpass.dispatch_workgroups(x, y, z);
+            }
+            gpu.queue.submit(Some(encoder.finish()));
+            gpu.wait_idle();
+            (buffer, start.elapsed())
+        }
+        ClearStrategy::InitZeros => {
+            let start = Instant::now();
+            let buffer = filled(0);
+            gpu.queue.submit(std::iter::empty());
+            gpu.wait_idle();
+            (buffer, start.elapsed())
+        }
+    };
+
+    (pollster::block_on(gpu.read_buffer(&buffer)), time)
+}
+
+/// Achieved GFLOP/s of `entry` of `gpu` over `len` elements doing `flops_per_element` each,
+/// e.g. 512 for `fmaLoop_call` from `flops_func.wgsl`. `entry` only binds the data at binding 0.
+/// Uses the GPU timestamps when available, the wall-clock wait for the kernel otherwise.
+pub fn measure_gflops(gpu: &GpuConsts, entry: &str, flops_per_element: u64, len: usize) -> f64 {
+    gpu.warmup(entry);
+
+    let mut bindings = Bindings::initialize_one(vec![1; len.max(1)]);
+    let stage = Stage::new(entry, 1).dispatch(gpu.dispatch_for(len).unwrap());
+    let bc = BufCoder::initialize_stages(gpu, &mut bindings, &[stage]);
+    let (_, timings) = pollster::block_on(gpu.run_with_timings(&bc)).unwrap();
+
+    let flops = flops_per_element as f64 * len as f64;
+    flops / timings.compute.as_secs_f64() / 1e9
+}
+
+/// Cold-start cost of a `GpuConsts::initialaze` with the shader `filename`: instance,
+/// adapter and device creation and the shader compilation. Dropping it isn't included.
+pub fn measure_init_time(filename: &str) -> Result<Duration, GpuError> {
+    let start = Instant::now();
+    let gpu = pollster::block_on(GpuConsts::initialaze(filename))?;
+    let time = start.elapsed();
+    drop(gpu);
+
+    Ok(time)
+}
+
+/// Median round trip of submitting one dispatch of the empty `warmup_func.wgsl` kernel
+/// and waiting for it, the fixed cost every GPU operation pays whatever its input size.
+pub fn measure_launch_latency(gpu: &GpuConsts) -> Duration {
+    const LAUNCHES: usize = 101;
+
+    let module = gpu.create_module("launch latency", include_str!("warmup_func.wgsl"));
+    let pipeline = gpu
+        .device
+        .create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+            label: gpu
+                .label("launch-latency", Some("launch latency"))
+                .as_deref(),
+            layout: None,
+            module: &module,
+            entry_point: "warmup_call",
+        });
+
+    let launch = || {
+        let start = Instant::now();
+        let mut encoder = gpu
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor { label: None });
+        {
+            let mut cpass =
+                encoder.begin_compute_pass(&wgpu::ComputePassDescriptor { label: None });
+            cpass.set_pipeline(&pipeline);
+            cpass.dispatch_workgroups(1, 1, 1);
+        }
+        let index = gpu.queue.submit(Some(encoder.finish()));
+        gpu.device
+            .poll(wgpu::Maintain::WaitForSubmissionIndex(index));
+        start.elapsed()
+    };
+
+    // The first launch also pays the driver and queue initialization, see `warmup`
+    launch();
+    let mut times: Vec<Duration> = (0..LAUNCHES).map(|_| launch()).collect();
+
+    times.sort();
+    times[LAUNCHES / 2]
+}
+
+/// Work of one kernel launch, counted by hand: e.g. `vectorAddition_call` over `n` elements
+/// reads `8 * n` bytes, writes `4 * n` and does `n` additions.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct KernelProfile {
+    /// Bytes read from and written to global memory.
+    pub bytes: u64,
+    pub flops: u64,
+}
+
+/// FLOPs per byte moved, where `profile` lies on the roofline; 0 when nothing is moved.
+pub fn arithmetic_intensity(profile: &KernelProfile) -> f64 {
+    if profile.bytes == 0 {
+        return 0.0;
+    }
+    profile.flops as f64 / profile.bytes as f64
+}
+
+/// Roofline bound of `profile` on a device of `peak_gflops` and `peak_bandwidth_gbs` (GB/s):
+/// the memory-bound `intensity * bandwidth` below the ridge point, the peak above it.
+pub fn attainable_gflops(
+    profile: &KernelProfile,
+    peak_gflops: f64,
+    peak_bandwidth_gbs: f64,
+) -> f64 {
+    (arithmetic_intensity(profile) * peak_bandwidth_gbs).min(peak_gflops)
+}
+
+/// Fraction of the roofline bound that `measured_gflops` reaches, e.g. from `measure_gflops`
+/// and peaks from `measure_gflops` with `fmaLoop_call` and `measure_transfer_bandwidth`.
+pub fn roofline_efficiency(
+    profile: &KernelProfile,
+    measured_gflops: f64,
+    peak_gflops: f64,
+    peak_bandwidth_gbs: f64,
+) -> f64 {
+    measured_gflops / attainable_gflops(profile, peak_gflops, peak_bandwidth_gbs)
+}
+
+/// Deterministic input of `len` values in `1..=100`, the same for the same `seed`.
+pub fn generate_data(len: usize, seed: u64) -> Vec<u32> {
+    let mut rng = StdRng::seed_from_u64(seed);
+
+    (0..len).map(|_| rng.gen_range(1..=100)).collect()
+}
+
+/// FNV-1a hash over the values of `data`.
+pub fn checksum(data: &[u32]) -> u64 {
+    data.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &x| {
+        (hash ^ x as u64).wrapping_mul(0x0000_0100_0000_01b3)
+    })
+}
+
+/// Checksum of the GPU output of `op` for `generate_data(len, seed)`,
+/// to be snapshotted so that changes of a kernel's output are noticed.
+pub fn output_checksum(gpu: &GpuConsts, op: Op, len: usize, seed: u64) -> u64 {
+    checksum(&op.run_gpu(gpu, &generate_data(len, seed)))
+}
+
+/// Element-wise `a + b` of the first `cap` elements, wrapping on overflow like the GPU.
+pub fn add_two_vec(a: &[u32], b: &[u32], cap: usize) -> Vec<u32> {
+    a[..cap]
+        .iter()
+        .zip(&b[..cap])
+        .map(|(&x, &y)| x.wrapping_add(y))
+        .collect()
+}
+
+/// `add_two_vec` that returns `None` if any element overflows.
+pub fn checked_add_two_vec(a: &[u32], b: &[u32], cap: usize) -> Option<Vec<u32>> {
+    a[..cap]
+        .iter()
+        .zip(&b[..cap])
+        .map(|(&x, &y)| x.checked_add(y))
+        .collect()
+}
+
+pub fn batch_add_two_vec(a: &[u32], b: &[u32], cap: usize, batch: u32) {
+    for _ in 0..batch {
+        add_two_vec(a, b, cap);
+    }
+}
+
+/// `batch_add_two_vec` with fresh inputs from `gen` for every iteration, so no iteration
+/// can reuse the work of a previous one.
+pub fn batch_with<F: FnMut(usize) -> (Vec<u32>, Vec<u32>)>(mut gen: F, batch: u32) {
+    for i in 0..batch as usize {
+        let (a, b) = gen(i);
+        std::hint::black_box(add_two_vec(&a, &b, a.len().min(b.len())));
+    }
+}
+
+/// Sum of the first `cap` elements, wrapping on overflow like the GPU.
+pub fn sum_vec(a: &[u32], cap: usize) -> u32 {
+    a[..cap].iter().fold(0, |sum, &x| sum.wrapping_add(x))
+}
+
+/// `sum_vec` over all of `a`, 8 lanes at a time with one horizontal add at the end.
+/// The `len % 8` elements that don't fill a lane vector are added one by one.
+/// Lane adds and `reduce_sum` wrap, the tail wraps the same way.
+#[cfg(feature = "simd")]
+pub fn simd_sum_vec(a: &[u32]) -> u32 {
+    use std::simd::{num::SimdUint, u32x8};
+
+    let chunks = a.chunks_exact(u32x8::LEN);
+    let tail = sum_vec(chunks.remainder(), chunks.remainder().len());
+    let lanes = chunks.fold(u32x8::splat(0), |acc, chunk| acc + u32x8::from_slice(chunk));
+
+    lanes.reduce_sum().wrapping_add(tail)
+}
+
+pub fn batch_sum_vec(a: &[u32], cap: usize, batch: u32) {
+    for _ in 0..batch {
+        sum_vec(a, cap);
+    }
+}
+
+pub fn add_two_vec_i32(a: &[i32], b: &[i32], cap: usize) -> Vec<i32> {
+    // Wraps on overflow like WGSL `i32` arithmetic does
+    a[..cap]
+        .iter()
+        .zip(&b[..cap])
+        .map(|(&x, &y)| x.wrapping_add(y))
+        .collect()
+}
+
+/// Reference for `gpu_strided_map`, `stride * count <= a.len()`.
+pub fn strided_map_vec(a: &[u32], stride: usize, count: usize) -> Vec<u32> {
+    a.iter()
+        .step_by(stride)
+        .take(count)
+        .map(|&x| x.wrapping_add(1))
+        .collect()
+}
+
+/// Reference for `gpu_window_sum`: the window of `a[i]` is `a[(i + 1).saturating_sub(window)..=i]`,
+/// empty when `window` is 0.
+pub fn window_sum_vec(a: &[u32], window: usize) -> Vec<u32> {
+    (0..a.len())
+        .map(|i| {
+            a[(i + 1).saturating_sub(window)..=i]
+                .iter()
+                .fold(0u32, |sum, &x| sum.wrapping_add(x))
+        })
+        .collect()
+}
+
+/// Compensated (Kahan) sum of `a`, the reference for `gpu_atomic_sum_f32`: the rounding
+/// error of every addition is carried into the next one.
+pub fn kahan_sum_f32(a: &[f32]) -> f32 {
+    let (sum, _) = a.iter().fold((0.0f32, 0.0f32), |(sum, compensation), &x| {
+        let y = x - compensation;
+        let t = sum + y;
+        (t, (t - sum) - y)
+    });
+    sum
+}
+
+/// Reference for `gpu_argmax`: `(max, index)` with the lowest index among equal maxima,
+/// where `max_by_key` alone would pick the highest.
+pub fn cpu_argmax(a: &[u32]) -> Option<(u32, u32)> {
+    a.iter()
+        .enumerate()
+        .max_by_key(|&(i, &x)| (x, std::cmp::Reverse(i)))
+        .map(|(i, &x)| (x, i as u32))
+}
+
+/// Sum of `a` added in order into an `f32`, rounding at every addition.
+pub fn naive_sum_f32(a: &[f32]) -> f32 {
+    a.iter().sum()
+}
+
+/// Sum of `a` accumulated in an `f64`, the "truth" of `compare_precision`.
+pub fn f64_sum_f32(a: &[f32]) -> f64 {
+    a.iter().map(|&x| x as f64).sum()
+}
+
+/// Reference for `gpu_saxpy`, over the common length of `x` and `y`.
+pub fn saxpy(a: f32, x: &[f32], y: &[f32]) -> Vec<f32> {
+    x.iter().zip(y).map(|(&x, &y)| a * x + y).collect()
+}
+
+/// Reference for `gpu_add_two_vec_f16`.
+pub fn add_two_vec_f32(a: &[f32], b: &[f32]) -> Vec<f32> {
+    a.iter().zip(b).map(|(&x, &y)| x + y).collect()
+}
+
+pub fn sum_vec_i32(a: &[i32]) -> i64 {
+    a.iter().map(|&x| x as i64).sum()
+}
+
+/// Sums `a` in blocks of `block` elements, so each partial sum is computed over data
+/// that stays in cache, then combines the partial sums.
+pub fn blocked_sum_vec(a: &[u32], block: usize) -> u64 {
+    assert!(block > 0, "block size must be positive");
+
+    a.chunks(block)
+        .map(|chunk| chunk.iter().map(|&x| x as u64).sum::<u64>())
+        .sum()
+}
+
+/// Sum of `a` 8 elements per iteration into 8 independent accumulators, so the additions
+/// don't wait for each other, then combined; the `len % 8` rest is added one by one.
+pub fn unrolled_sum_vec(a: &[u32]) -> u64 {
+    let chunks = a.chunks_exact(8);
+    let tail: u64 = chunks.remainder().iter().map(|&x| x as u64).sum();
+
+    let mut acc = [0u64; 8];
+    for chunk in chunks {
+        acc[0] += chunk[0] as u64;
+        acc[1] += chunk[1] as u64;
+        acc[2] += chunk[2] as u64;
+        acc[3] += chunk[3] as u64;
+        acc[4] += chunk[4] as u64;
+        acc[5] += chunk[5] as u64;
+        acc[6] += chunk[6] as u64;
+        acc[7] += chunk[7] as u64;
+    }
+
+    acc.iter().sum::<u64>() + tail
+}
+
+/// Sum of `a` split into `threads` chunks, each summed on its own `std::thread::scope`
+/// thread, for a parallel CPU baseline without rayon.
+pub fn threaded_sum_vec(a: &[u32], threads: usize) -> u64 {
+    assert!(threads > 0, "at least one thread is needed");
+    if a.is_empty() {
+        return 0;
+    }
+
+    let chunk = a.len().div_ceil(threads);
+    std::thread::scope(|scope| {
+        let handles: Vec<_> = a
+            .chunks(chunk)
+            .map(|chunk| scope.spawn(move || chunk.iter().map(|&x| x as u64).sum::<u64>()))
+            .collect();
+
+        handles
+            .into_iter()
+            .map(|handle| handle.join().unwrap())
+            .sum()
+    })
+}
+
+/// Ranges shorter than this are summed in a loop by `optimized_sum_vec`, splitting them
+/// further costs more in calls than it saves.
+pub const OPTIMIZED_SUM_BASE: usize = 64;
+
+/// Wrapping sum of `arr[start..=end]`, split in halves down to `OPTIMIZED_SUM_BASE` elements.
+pub fn optimized_sum_vec(arr: &[u32], start: usize, end: usize) -> u32 {
+    if end - start < OPTIMIZED_SUM_BASE {
+        return sum_vec(&arr[start..=end], end - start + 1);
+    }
+    optimized_sum_vec(arr, start, (end - start) / 2 + start).wrapping_add(optimized_sum_vec(
+        arr,
+        (end - start) / 2 + start + 1,
+        end,
+    ))
+}
+
+/// `optimized_sum_vec` splitting all the way down to single elements, for comparison.
+pub fn recursive_sum_vec(arr: &[u32], start: usize, end: usize) -> u32 {
+    if end == start {
+        return arr[end];
+    }
+    if end - start == 1 {
+        return arr[start].wrapping_add(arr[end]);
+    }
+    recursive_sum_vec(arr, start, (end - start) / 2 + start).wrapping_add(recursive_sum_vec(
+        arr,
+        (end - start) / 2 + start + 1,
+        end,
+    ))
+}
+
+/// Times of `sum_vec`, `recursive_sum_vec` and `optimized_sum_vec` over the non-empty `arr`.
+pub fn compare_cpu_sums(arr: &[u32]) -> (Duration, Duration, Duration) {
+    let time = |sum: &dyn Fn() -> u32| {
+        let start = Instant::now();
+        std::hint::black_box(sum());
+        start.elapsed()
+    };
+
+    (
+        time(&|| sum_vec(arr, arr.len())),
+        time(&|| recursive_sum_vec(arr, 0, arr.len() - 1)),
+        time(&|| optimized_sum_vec(arr, 0, arr.len() - 1)),
+    )
+}
+
+pub fn batch_optimized_sum_vec(arr: &[u32], start: usize, end: usize, batch: u32) {
+    for _ in 0..batch {
+        optimized_sum_vec(arr, start, end);
+    }
+}
+
+/// Acceptable error when comparing float results: `|a - b| <= abs + rel * |b|`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Tolerance {
+    pub rel: f32,
+    pub abs: f32,
+}
+
+impl Tolerance {
+    /// Two roundings to `f16`, of the inputs and of the sum, each off by up to 2^-11;
+    /// `abs` is the smallest normal `f16`, below which the precision is absolute.
+    pub const F16: Tolerance = Tolerance::new(2e-3, 6.1e-5);
+
+    pub const fn new(rel: f32, abs: f32) -> Self {
+        Tolerance { rel, abs }
+    }
+
+    /// Checks `actual` against the `expected` reference value.
+    pub fn accepts(&self, actual: f32, expected: f32) -> bool {
+        (actual - expected).abs() <= self.abs + self.rel * expected.abs()
+    }
+
+    /// Checks two slices element by element; slices of different length never match.
+    pub fn all_close(&self, actual: &[f32], expected: &[f32]) -> bool {
+        actual.len() == expected.len()
+            && actual
+                .iter()
+                .zip(expected)
+                .all(|(&a, &b)| self.accepts(a, b))
+    }
+}
+
+/// An element where a result and its reference differ.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Divergence {
+    pub index: usize,
+    pub actual: f64,
+    pub expected: f64,
+}
+
+/// Why a result doesn't match its reference file, see `compare_against_reference`.
+#[derive(Debug)]
+pub enum Mismatch {
+    /// The reference couldn't be read.
+    Io(std::io::Error),
+    /// The reference holds something that isn't a value of the compared type.
+    Parse(String),
+    /// The result has `actual` elements, the reference `expected`.
+    Length { actual: usize, expected: usize },
+    /// `count` elements differ, `first` by index and `largest` by absolute difference.
+    Values {
+        first: Divergence,
+        largest: Divergence,
+        count: usize,
+    },
+}
+
+impl std::fmt::Display for Mismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Mismatch::Io(err) => write!(f, "reference: {}", err),
+            Mismatch::Parse(value) => write!(f, "reference: invalid value {:?}", value),
+            Mismatch::Length { actual, expected } => write!(
+                f,
+                "result has {} elements, reference {}",
+                actual, expected
+            ),
+            Mismatch::Values {
+                first,
+                largest,
+                count,
+            } => write!(
+                f,
+                "{} elements differ, first at {} ({} instead of {}), largest at {} ({} instead of {})",
+                count,
+                first.index,
+                first.actual,
+                first.expected,
+                largest.index,
+                largest.actual,
+                largest.expected
+            ),
+        }
+    }
+}
+
+impl std::error::Error for Mismatch {}
+
+/// Compares `result` with the reference at `ref_path`, e.g. dumped by the CUDA version:
+/// comma or whitespace separated values for a `.csv` file, raw little-endian `u32`s otherwise.
+pub fn compare_against_reference(result: &[u32], ref_path: &Path) -> Result<(), Mismatch> {
+    let expected: Vec<u32> = load_reference(ref_path)?;
+
+    compare_values(result, &expected, |actual, expected| actual == expected)
+}
+
+/// `compare_against_reference` for `f32` results, values are equal within `tolerance`.
+pub fn compare_against_reference_f32(
+    result: &[f32],
+    ref_path: &Path,
+    tolerance: Tolerance,
+) -> Result<(), Mismatch> {
+    let expected: Vec<f32> = load_reference(ref_path)?;
+
+    compare_values(result, &expected, |actual, expected| {
+        tolerance.accepts(actual, expected)
+    })
+}
+
+fn load_reference<T: bytemuck::Pod + FromStr>(path: &Path) -> Result<Vec<T>, Mismatch> {
+    let bytes = std::fs::read(path).map_err(Mismatch::Io)?;
+
+    if path.extension().is_some_and(|ext| ext == "csv") {
+        let text = String::from_utf8(bytes).map_err(|err| Mismatch::Parse(err.to_string()))?;
+        text.split(|c: char| c == ',' || c.is_whitespace())
+            .filter(|value| !value.is_empty())
+            .map(|value| {
+                value
+                    .parse()
+                    .map_err(|_| Mismatch::Parse(value.to_string()))
+            })
+            .collect()
+    } else {
+        // Little-endian whatever the host, `T` is a `u32` or an `f32` with the same bytes
+        if bytes.len() % 4 != 0 {
+            return Err(Mismatch::Parse(format!(
+                "{} bytes, not a multiple of 4",
+                bytes.len()
+            )));
+        }
+        Ok(bytes
+            .chunks_exact(4)
+            .map(|chunk| bytemuck::cast(u32::from_le_bytes(chunk.try_into().unwrap())))
+            .collect())
+    }
+}
+
+fn compare_values<T: Copy + Into<f64>>(
+    actual: &[T],
+    expected: &[T],
+    equal: impl Fn(T, T) -> bool,
+) -> Result<(), Mismatch> {
+    if actual.len() != expected.len() {
+        return Err(Mismatch::Length {
+            actual: actual.len(),
+            expected: expected.len(),
+        });
+    }
+
+    let mut divergences = actual
+        .iter()
+        .zip(expected)
+        .enumerate()
+        .filter(|(_, (&a, &b))| !equal(a, b))
+        .map(|(index, (&a, &b))| Divergence {
+            index,
+            actual: a.into(),
+            expected: b.into(),
+        });
+
+    let Some(first) = divergences.next() else {
+        return Ok(());
+    };
+    let (largest, count) = divergences.fold((first, 1), |(largest, count), divergence| {
+        let diff = |d: &Divergence| (d.actual - d.expected).abs();
+        if diff(&divergence) > diff(&largest) {
+            (divergence, count + 1)
+        } else {
+            (largest, count + 1)
+        }
+    });
+
+    Err(Mismatch::Values {
+        first,
+        largest,
+        count,
+    })
+}
+
+/// Markdown table of the first `max_report` elements where `gpu` differs from `cpu`, with
+/// the index, both values and `gpu - cpu`, followed by the total number of mismatches.
+/// Only the common prefix is compared when the lengths differ, which the report mentions.
+pub fn diff_report(cpu: &[u32], gpu: &[u32], max_report: usize) -> String {
+    use std::fmt::Write;
+
+    let mismatches: Vec<(usize, u32, u32)> = cpu
+        .iter()
+        .zip(gpu)
+        .enumerate()
+        .filter(|(_, (c, g))| c != g)
+        .map(|(index, (&c, &g))| (index, c, g))
+        .collect();
+
+    let mut report = String::new();
+    if cpu.len() != gpu.len() {
+        writeln!(
+            report,
+            "Lengths differ: CPU {}, GPU {}",
+            cpu.len(),
+            gpu.len()
+        )
+        .unwrap();
+    }
+    if !mismatches.is_empty() {
+        writeln!(report, "| Index | CPU | GPU | Delta |").unwrap();
+        writeln!(report, "| :--- | :---: | :---: | :---: |").unwrap();
+        for &(index, c, g) in mismatches.iter().take(max_report) {
+            writeln!(
+                report,
+                "| {} | {} | {} | {} |",
+                index,
+                c,
+                g,
+                g as i64 - c as i64
+            )
+            .unwrap();
+        }
+    }
+    write!(
+        report,
+        "{} mismatches, {} shown",
+        mismatches.len(),
+        mismatches.len().min(max_report)
+    )
+    .unwrap();
+
+    report
+}
+
+#[cfg(test)]
+mod tests;
@@ -11,7 +11,7 @@ fn main() {
 
     let gpu = pollster::block_on(GpuConsts::initialaze("src/vec_func.wgsl")).unwrap();
     let bc = BufCoder::initialize(&gpu, &mut bindings, "batch1000_vectorAddition_call", 3);
-    let res = pollster::block_on(gpu.run(&bc)).unwrap();
+    let res = pollster::block_on(gpu.run::<u32>(&bc)).unwrap();
 
     println!("wgsl result {:?}", res);
 
@@ -29,7 +29,7 @@ fn main() {
     let mut bindings: Bindings = Bindings::initialize_two(vec1, vec2);
     let gpu = pollster::block_on(GpuConsts::initialaze("src/sum_func.wgsl")).unwrap();
     let bc = BufCoder::initialize(&gpu, &mut bindings, "vectorSum_call", 2);
-    let res = pollster::block_on(gpu.run(&bc)).unwrap();
+    let res = pollster::block_on(gpu.run::<u32>(&bc)).unwrap();
 
     println!("Sum vec(wgsl) {:?}", res);
 
@@ -48,7 +48,7 @@ fn main() {
     let mut bindings: Bindings = Bindings::initialize_two(vec1, vec2);
     let gpu = pollster::block_on(GpuConsts::initialaze("src/optimized_sum_func.wgsl")).unwrap();
     let bc = BufCoder::initialize(&gpu, &mut bindings, "optimized_vectorSum_call", 2);
-    let res = pollster::block_on(gpu.run(&bc)).unwrap();
+    let res = pollster::block_on(gpu.run::<u32>(&bc)).unwrap();
 
     println!("Optimized sum vec(WGSL) {:?}", res);
 }
@@ -1,52 +1,63 @@
-@group(0) @binding(0) var<storage, read_write> output: array<u32>;
+@group(0) @binding(0) var<storage, read_write> output: array<atomic<u32>>;
 @group(0) @binding(1) var<storage, read> input_a: array<u32>;
 
-fn optimized_vectorSum(global_id: vec3u) {
-	let start: u32 = 0u;
-	let end: u32 = arrayLength(&input_a) - 1u;
+var<workgroup> partial: array<u32, 256>;
 
-	var stack: array<vec2<u32>, 32>;
-
-	var stackPtr: u32 = 0u;
-	stack[stackPtr] = vec2<u32>(start, end);
-
-	if (stackPtr >= global_id.x) {
-		let currentRange: vec2<u32> = stack[stackPtr];
-		stackPtr = stackPtr - 1u;
-
-		let currentStart: u32 = currentRange.x;
-		let currentEnd: u32 = currentRange.y;
-
-		if (currentStart == currentEnd) {
-			output[0] += input_a[currentStart];
-		} else if (currentStart < currentEnd) {
-
-			let mid: u32 = (currentStart + currentEnd) / 2u;
+// See `flat_index` of `vec_func.wgsl`.
+fn flat_index(global_id: vec3u, num_workgroups: vec3u) -> u32 {
+  return global_id.x + (global_id.y + global_id.z * num_workgroups.y) * num_workgroups.x * 256u;
+}
 
-			stackPtr = stackPtr + 1u;
-			stack[stackPtr] = vec2<u32>(currentStart, mid);
+// Divide and conquer within a workgroup: its 256 values are added pairwise in shared memory,
+// halving them until one is left, so only one atomic add per workgroup reaches output[0].
+// Wraps on overflow like `optimized_sum_vec`.
+fn optimized_vectorSum(idx: u32, local: u32) {
+	var value = 0u;
+	if idx < arrayLength(&input_a) {
+		value = input_a[idx];
+	}
+	partial[local] = value;
+	workgroupBarrier();
 
-			stackPtr = stackPtr + 1u;
-			stack[stackPtr] = vec2<u32>(mid + 1u, currentEnd);
+	for (var stride = 128u; stride > 0u; stride = stride / 2u) {
+		if local < stride {
+			partial[local] += partial[local + stride];
 		}
+		workgroupBarrier();
+	}
+
+	if local == 0u {
+		atomicAdd(&output[0], partial[0]);
 	}
 }
 
 @compute @workgroup_size(256)
-fn optimized_vectorSum_call(@builtin(global_invocation_id) global_id: vec3u) {
-  optimized_vectorSum(global_id);
+fn optimized_vectorSum_call(
+  @builtin(global_invocation_id) global_id: vec3u,
+  @builtin(num_workgroups) num_workgroups: vec3u,
+  @builtin(local_invocation_index) local: u32,
+) {
+  optimized_vectorSum(flat_index(global_id, num_workgroups), local);
 }
 
 @compute @workgroup_size(256)
-fn batch1000_optimized_vectorSum_call(@builtin(global_invocation_id) global_id: vec3u) {
+fn batch1000_optimized_vectorSum_call(
+  @builtin(global_invocation_id) global_id: vec3u,
+  @builtin(num_workgroups) num_workgroups: vec3u,
+  @builtin(local_invocation_index) local: u32,
+) {
 	for (var i: u32 = 0u; i < 1000u; i = i + 1u) {
-    optimized_vectorSum(global_id);
+    optimized_vectorSum(flat_index(global_id, num_workgroups), local);
   }
 }
 
 @compute @workgroup_size(256)
-fn batch100000_optimized_vectorSum_call(@builtin(global_invocation_id) global_id: vec3u) {
+fn batch100000_optimized_vectorSum_call(
+  @builtin(global_invocation_id) global_id: vec3u,
+  @builtin(num_workgroups) num_workgroups: vec3u,
+  @builtin(local_invocation_index) local: u32,
+) {
 	for (var i: u32 = 0u; i < 10000u; i = i + 1u) {
-    optimized_vectorSum(global_id);
+    optimized_vectorSum(flat_index(global_id, num_workgroups), local);
   }
 }
\ No newline at end of file
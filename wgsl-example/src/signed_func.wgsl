@@ -0,0 +1,36 @@
+@group(0) @binding(0) var<storage, read_write> output: array<i32>;
+@group(0) @binding(1) var<storage, read> input_a: array<i32>;
+@group(0) @binding(2) var<storage, read> input_b: array<i32>;
+
+// See `flat_index` of `vec_func.wgsl`.
+fn flat_index(global_id: vec3u, num_workgroups: vec3u) -> u32 {
+  return global_id.x + (global_id.y + global_id.z * num_workgroups.y) * num_workgroups.x * 256u;
+}
+
+// Overflowing `i32` arithmetic wraps around, like `wrapping_add` on the host.
+fn vectorAdditionI32(idx: u32) {
+  if idx < arrayLength(&output) {
+    output[idx] = input_a[idx] + input_b[idx];
+  }
+}
+
+@compute @workgroup_size(256)
+fn vectorAdditionI32_call(
+  @builtin(global_invocation_id) global_id: vec3u,
+  @builtin(num_workgroups) num_workgroups: vec3u,
+) {
+  vectorAdditionI32(flat_index(global_id, num_workgroups));
+}
+
+// A single invocation accumulates all elements in order, so the result is exact
+// as long as every partial sum fits into an `i32`.
+@compute @workgroup_size(1)
+fn vectorSumI32_call(@builtin(global_invocation_id) global_id: vec3u) {
+  if global_id.x == 0u {
+    var acc: i32 = 0;
+    for (var i: u32 = 0u; i < arrayLength(&input_a); i = i + 1u) {
+      acc = acc + input_a[i];
+    }
+    output[0] = acc;
+  }
+}
@@ -0,0 +1,28 @@
+// Strided map: output[i] = input[i * stride] + 1. With stride 1 neighbouring invocations
+// read neighbouring words, larger strides spread the reads of a workgroup over more memory.
+struct Params {
+  count: u32,
+  stride: u32,
+  _pad0: u32,
+  _pad1: u32,
+}
+
+@group(0) @binding(0) var<storage, read_write> output: array<u32>;
+@group(0) @binding(1) var<storage, read> input: array<u32>;
+@group(1) @binding(0) var<uniform> params: Params;
+
+// See `flat_index` of `vec_func.wgsl`.
+fn flat_index(global_id: vec3u, num_workgroups: vec3u) -> u32 {
+  return global_id.x + (global_id.y + global_id.z * num_workgroups.y) * num_workgroups.x * 256u;
+}
+
+@compute @workgroup_size(256)
+fn stridedIncrement_call(
+  @builtin(global_invocation_id) global_id: vec3u,
+  @builtin(num_workgroups) num_workgroups: vec3u,
+) {
+  let idx = flat_index(global_id, num_workgroups);
+  if idx < params.count {
+    output[idx] = input[idx * params.stride] + 1u;
+  }
+}
@@ -1,27 +1,42 @@
-@group(0) @binding(0) var<storage, read_write> output: array<u32>;
+@group(0) @binding(0) var<storage, read_write> output: array<atomic<u32>>;
 @group(0) @binding(1) var<storage, read> input_a: array<u32>;
 
-fn vectorSum(global_id: vec3u) {
-  for (var i: u32 = global_id.x; i < arrayLength(&input_a); i = i + 1u) {
-    output[0] += input_a[i];
+// See `flat_index` of `vec_func.wgsl`.
+fn flat_index(global_id: vec3u, num_workgroups: vec3u) -> u32 {
+  return global_id.x + (global_id.y + global_id.z * num_workgroups.y) * num_workgroups.x * 256u;
+}
+
+// Every invocation adds its own element to output[0], wrapping on overflow like `sum_vec`.
+fn vectorSum(idx: u32) {
+  if idx < arrayLength(&input_a) {
+    atomicAdd(&output[0], input_a[idx]);
   }
 }
 
 @compute @workgroup_size(256)
-fn vectorSum_call(@builtin(global_invocation_id) global_id: vec3u) {
-  vectorSum(global_id);
+fn vectorSum_call(
+  @builtin(global_invocation_id) global_id: vec3u,
+  @builtin(num_workgroups) num_workgroups: vec3u,
+) {
+  vectorSum(flat_index(global_id, num_workgroups));
 }
 
 @compute @workgroup_size(256)
-fn batch1000_vectorSum_call(@builtin(global_invocation_id) global_id: vec3u) {
+fn batch1000_vectorSum_call(
+  @builtin(global_invocation_id) global_id: vec3u,
+  @builtin(num_workgroups) num_workgroups: vec3u,
+) {
   for (var i: u32 = 0u; i < 1000u; i = i + 1u) {
-    vectorSum(global_id);
+    vectorSum(flat_index(global_id, num_workgroups));
   }
 }
 
 @compute @workgroup_size(256)
-fn batch100000_vectorSum_call(@builtin(global_invocation_id) global_id: vec3u) {
+fn batch100000_vectorSum_call(
+  @builtin(global_invocation_id) global_id: vec3u,
+  @builtin(num_workgroups) num_workgroups: vec3u,
+) {
   for (var i: u32 = 0u; i < 10000u; i = i + 1u) {
-    vectorSum(global_id);
+    vectorSum(flat_index(global_id, num_workgroups));
   }
 }
\ No newline at end of file
@@ -0,0 +1,2319 @@
+#[cfg(feature = "trace-resources")]
+use std::fmt::Debug;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+use super::*;
+
+// Tests that need a GPU return early where there is none, as on most CI machines.
+fn gpu(shader_file: &str) -> Option<GpuConsts> {
+    pollster::block_on(GpuConsts::initialaze(shader_file)).ok()
+}
+
+#[test]
+fn indirect_dispatch_runs_the_workgroups_a_kernel_counted() {
+    let Some(gpu) = gpu(Op::Add.shader_file()) else {
+        return;
+    };
+    let a = generate_data(1000, 1);
+    let b = generate_data(1000, 2);
+
+    let mut counts = Bindings::initialize_two(vec![0; 3], a.clone());
+    let counter = BufCoder::initialize_stages(
+        &gpu,
+        &mut counts,
+        &[Stage::new("vectorAddition_dispatch_size_call", 2)
+            .dispatch(Dispatch::Workgroups(1, 1, 1))],
+    );
+    let mut bindings = Bindings::initialize_three(vec![0; a.len()], a.clone(), b.clone());
+    let bc = BufCoder::initialize_indirect(
+        &gpu,
+        &mut bindings,
+        "vectorAddition_call",
+        3,
+        counter.storage_buffer(),
+        0,
+    );
+
+    assert_eq!(
+        pollster::block_on(gpu.run::<u32>(&bc)).unwrap(),
+        add_two_vec(&a, &b, a.len())
+    );
+    assert_eq!(
+        pollster::block_on(gpu.run::<u32>(&counter)).unwrap(),
+        [4, 1, 1]
+    );
+}
+
+#[test]
+fn tolerance_accepts_near_zero_differences_through_the_absolute_term() {
+    let tolerance = Tolerance::new(1e-4, 1e-6);
+
+    // Relative to a reference of 0, any difference would be too large
+    assert!(tolerance.accepts(5e-7, 0.0));
+    assert!(!tolerance.accepts(5e-6, 0.0));
+    assert!(tolerance.accepts(1000.05, 1000.0));
+    assert!(!tolerance.accepts(1000.5, 1000.0));
+    assert!(!tolerance.all_close(&[1.0], &[1.0, 2.0]));
+}
+
+#[test]
+fn dispatch_after_warmup_still_works() {
+    let Some(gpu) = gpu(Op::Add.shader_file()) else {
+        return;
+    };
+    gpu.warmup(Op::Add.entry_point());
+
+    let data = generate_data(300, 3);
+    assert_eq!(Op::Add.run_gpu(&gpu, &data), Op::Add.run_cpu(&data));
+}
+
+#[test]
+fn stages_of_one_submission_see_the_writes_of_the_previous_ones() {
+    let Some(gpu) = gpu(Op::Add.shader_file()) else {
+        return;
+    };
+    let a = generate_data(1000, 4);
+    let b = generate_data(1000, 5);
+    let increment = gpu.create_module("increment", &gen_unary_kernel("x + 1u"));
+
+    let mut bindings = Bindings::initialize_three(vec![0; a.len()], a.clone(), b.clone());
+    let stages = [
+        Stage::new("vectorAddition_call", 3).dispatch(Dispatch::Workgroups(
+            workgroup_count(a.len()),
+            1,
+            1,
+        )),
+        Stage::new(UNARY_ENTRY, 1)
+            .dispatch(Dispatch::Workgroups(workgroup_count(a.len()), 1, 1))
+            .module(&increment),
+    ];
+    let bc = BufCoder::initialize_stages(&gpu, &mut bindings, &stages);
+
+    let expected: Vec<u32> = add_two_vec(&a, &b, a.len()).iter().map(|x| x + 1).collect();
+    assert_eq!(pollster::block_on(gpu.run::<u32>(&bc)).unwrap(), expected);
+}
+
+#[test]
+fn run_with_hands_the_mapped_result_to_the_closure() {
+    let Some(gpu) = gpu(Op::Add.shader_file()) else {
+        return;
+    };
+    let data = generate_data(1000, 6);
+
+    let mut bindings = Op::Add.bindings(&data);
+    let bc = BufCoder::initialize_stages(
+        &gpu,
+        &mut bindings,
+        &[
+            Stage::new(Op::Add.entry_point(), 3).dispatch(Dispatch::Workgroups(
+                workgroup_count(data.len()),
+                1,
+                1,
+            )),
+        ],
+    );
+
+    assert_eq!(
+        pollster::block_on(gpu.run_with(&bc, checksum)),
+        checksum(&Op::Add.run_cpu(&data))
+    );
+}
+
+#[test]
+fn unrolled_sum_vec_equals_sum_vec_at_every_remainder() {
+    // Every remainder of the 8 accumulators, and one length far past them
+    for len in (0..=8).chain([100_003]) {
+        let data = generate_data(len, 7);
+        assert_eq!(
+            unrolled_sum_vec(&data),
+            sum_vec(&data, len) as u64,
+            "len {}",
+            len
+        );
+    }
+
+    // Where the `u32` sum would wrap, the `u64` accumulators don't
+    let big = vec![u32::MAX; 17];
+    assert_eq!(unrolled_sum_vec(&big), 17 * u32::MAX as u64);
+}
+
+#[test]
+fn blocked_sum_vec_equals_sum_vec_at_any_block_size() {
+    let data = generate_data(1000, 6);
+    // One element per block, blocks that don't divide the length, one block for everything
+    for block in [1, 7, 256, 1000, 5000] {
+        assert_eq!(
+            blocked_sum_vec(&data, block),
+            sum_vec(&data, data.len()) as u64,
+            "block {}",
+            block
+        );
+    }
+    assert_eq!(blocked_sum_vec(&[], 64), 0);
+
+    // Where the `u32` sum would wrap, the `u64` partial sums don't
+    let big = vec![u32::MAX; 17];
+    assert_eq!(blocked_sum_vec(&big, 4), 17 * u32::MAX as u64);
+}
+
+#[test]
+fn output_checksum_is_stable_across_runs() {
+    for op in [Op::Add, Op::Sum, Op::OptimizedSum] {
+        let Some(gpu) = gpu(op.shader_file()) else {
+            return;
+        };
+
+        for len in [0, 1, 255, 1000] {
+            let first = output_checksum(&gpu, op, len, 8);
+            assert_eq!(
+                output_checksum(&gpu, op, len, 8),
+                first,
+                "{:?} over {}",
+                op,
+                len
+            );
+            assert_eq!(first, checksum(&op.run_cpu(&generate_data(len, 8))));
+        }
+    }
+}
+
+#[test]
+fn bindings_other_than_the_output_can_be_read_back() {
+    let Some(gpu) = gpu(Op::Add.shader_file()) else {
+        return;
+    };
+    let a = vec![1, u32::MAX, 3];
+    let b = vec![1, 1, 1];
+
+    let mut bindings = Bindings::initialize_four(vec![0; 3], a.clone(), b, vec![0]);
+    let bc = BufCoder::initialize(&gpu, &mut bindings, "vectorAdditionChecked_call", 4);
+
+    assert_eq!(pollster::block_on(gpu.read_binding(&bc, 1)).unwrap(), a);
+    // The kernel flags the wrapped lane in the last binding
+    assert_eq!(pollster::block_on(gpu.read_binding(&bc, 3)).unwrap(), [1]);
+}
+
+#[test]
+fn kernels_past_one_row_of_workgroups_still_see_every_element() {
+    let Some(gpu) = gpu(Op::Sum.shader_file()) else {
+        return;
+    };
+    // One element more than a single row of workgroups along x holds
+    let max = gpu.device.limits().max_compute_workgroups_per_dimension as usize;
+    let data = generate_data(max * 256 + 1, 3);
+    if gpu.can_run(data.len(), 2).is_err() {
+        return;
+    }
+
+    let plan = gpu
+        .plan_dispatch(workgroup_count(data.len()) as u64)
+        .unwrap();
+    assert_eq!(plan.dimensions, 2);
+    assert_eq!(Op::Sum.run_gpu(&gpu, &data), Op::Sum.run_cpu(&data));
+}
+
+#[test]
+fn compare_kernels_tells_whether_two_entries_agree() {
+    let Some(gpu) = gpu("src/atomic_func.wgsl") else {
+        return;
+    };
+    let data = generate_data(1000, 4);
+
+    // Both keep the largest element in output[0]
+    assert!(compare_kernels(&gpu, "atomicMax_call", "argmaxValue_call", &data).agree);
+    assert!(!compare_kernels(&gpu, "atomicMax_call", "countEven_call", &data).agree);
+}
+
+#[test]
+fn each_submission_can_be_waited_on_by_itself() {
+    let Some(gpu) = gpu(Op::Add.shader_file()) else {
+        return;
+    };
+    let small = generate_data(10, 1);
+    let large = generate_data(100_000, 2);
+    let submit = |bindings: &mut Bindings, len| {
+        let stage = Stage::new(Op::Add.entry_point(), 3).dispatch(gpu.dispatch_for(len).unwrap());
+        BufCoder::initialize_stages(&gpu, bindings, &[stage])
+    };
+
+    let mut small_bindings = Op::Add.bindings(&small);
+    let first = submit(&mut small_bindings, small.len());
+    let mut large_bindings = Op::Add.bindings(&large);
+    let second = submit(&mut large_bindings, large.len());
+
+    // Each wait only covers its own submission and the ones before it
+    gpu.wait_for(&first);
+    gpu.wait_for(&second);
+
+    assert_eq!(
+        pollster::block_on(gpu.run::<u32>(&first)).unwrap(),
+        Op::Add.run_cpu(&small)
+    );
+    assert_eq!(
+        pollster::block_on(gpu.run::<u32>(&second)).unwrap(),
+        Op::Add.run_cpu(&large)
+    );
+}
+
+#[test]
+fn cpu_fallback_wraps_like_the_gpu() {
+    let data = [u32::MAX, 2, 3];
+
+    assert_eq!(run_op_on(None, Op::Add, &data), [u32::MAX - 1, 4, 6]);
+    assert_eq!(run_op_on(None, Op::Sum, &data), [4]);
+    assert_eq!(run_op_on(None, Op::OptimizedSum, &data), [4]);
+}
+
+#[test]
+fn cpu_fallback_matches_the_gpu_for_every_op() {
+    // The large elements make every sum and a few additions wrap
+    let mut data = generate_data(1000, 6);
+    data[..3].fill(u32::MAX - 1);
+
+    for op in [Op::Add, Op::Sum, Op::OptimizedSum] {
+        let Some(gpu) = gpu(op.shader_file()) else {
+            return;
+        };
+        assert_eq!(
+            op.run_gpu(&gpu, &data),
+            run_op_on(None, op, &data),
+            "{:?}",
+            op
+        );
+    }
+}
+
+#[test]
+fn phase_timings_add_up_to_at_most_the_whole_round_trip() {
+    let Some(gpu) = gpu(Op::Add.shader_file()) else {
+        return;
+    };
+    let data = generate_data(100_000, 7);
+
+    let start = Instant::now();
+    let mut bindings = Op::Add.bindings(&data);
+    let stage =
+        Stage::new(Op::Add.entry_point(), 3).dispatch(gpu.dispatch_for(data.len()).unwrap());
+    let bc = BufCoder::initialize_stages(&gpu, &mut bindings, &[stage]);
+    let (result, timings) = pollster::block_on(gpu.run_with_timings(&bc)).unwrap();
+    let total = start.elapsed();
+
+    assert_eq!(result, Op::Add.run_cpu(&data));
+    assert!(timings.upload > Duration::ZERO);
+    assert!(timings.download > Duration::ZERO);
+    // GPU timestamps don't count the host side, the fallback wait is part of `total`
+    if !timings.gpu_timestamps {
+        assert!(timings.upload + timings.compute + timings.download <= total);
+    }
+}
+
+#[test]
+fn storage_buffers_are_mapped_directly_only_where_they_share_host_memory() {
+    let info = |device_type| AdapterInfo {
+        name: String::new(),
+        vendor: 0,
+        device: 0,
+        device_type,
+        driver: String::new(),
+        driver_info: String::new(),
+        backend: wgpu::Backend::Vulkan,
+    };
+    let mappable = wgpu::Features::MAPPABLE_PRIMARY_BUFFERS;
+
+    assert_eq!(
+        select_readback_path(&info(wgpu::DeviceType::IntegratedGpu), mappable),
+        ReadbackPath::Direct
+    );
+    assert_eq!(
+        select_readback_path(&info(wgpu::DeviceType::Cpu), mappable),
+        ReadbackPath::Direct
+    );
+    assert_eq!(
+        select_readback_path(&info(wgpu::DeviceType::DiscreteGpu), mappable),
+        ReadbackPath::Staging
+    );
+    assert_eq!(
+        select_readback_path(
+            &info(wgpu::DeviceType::IntegratedGpu),
+            wgpu::Features::empty()
+        ),
+        ReadbackPath::Staging
+    );
+}
+
+#[test]
+fn the_selected_readback_path_returns_the_result() {
+    let Some(gpu) = gpu(Op::Add.shader_file()) else {
+        return;
+    };
+    let data = generate_data(1000, 8);
+
+    assert_eq!(
+        gpu.readback_path(),
+        select_readback_path(gpu.adapter_info(), gpu.device.features())
+    );
+    assert_eq!(Op::Add.run_gpu(&gpu, &data), Op::Add.run_cpu(&data));
+}
+
+#[test]
+fn every_op_is_deterministic() {
+    let data = generate_data(10_000, 9);
+
+    for op in [Op::Add, Op::Sum, Op::OptimizedSum] {
+        let Some(gpu) = gpu(op.shader_file()) else {
+            return;
+        };
+        assert!(check_determinism(&gpu, op, &data, 5), "{:?}", op);
+    }
+}
+
+#[test]
+fn signed_kernels_match_the_cpu_on_negatives() {
+    let Some(gpu) = gpu(Op::Add.shader_file()) else {
+        return;
+    };
+    let a: Vec<i32> = (0..1000).map(|i| i * 7 % 201 - 100).collect();
+    let b: Vec<i32> = (0..1000).map(|i| -(i * 3 % 101)).collect();
+
+    assert_eq!(
+        gpu_add_two_vec_i32(&gpu, &a, &b),
+        add_two_vec_i32(&a, &b, a.len())
+    );
+    assert_eq!(gpu_sum_vec_i32(&gpu, &b) as i64, sum_vec_i32(&b));
+    assert_eq!(gpu_sum_vec_i32(&gpu, &a) as i64, sum_vec_i32(&a));
+    // Overflow past the most negative value wraps to the most positive one
+    assert_eq!(gpu_add_two_vec_i32(&gpu, &[i32::MIN], &[-1]), [i32::MAX]);
+}
+
+#[test]
+fn hybrid_sum_equals_the_full_sum_at_any_split() {
+    let Some(gpu) = gpu(Op::Sum.shader_file()) else {
+        return;
+    };
+    let data = generate_data(10_000, 10);
+    let sum: u64 = data.iter().map(|&x| x as u64).sum();
+
+    // Out of range fractions are clamped to the whole input on one side
+    for fraction in [-1.0, 0.0, 0.1, 0.5, 0.99, 1.0, 2.0] {
+        assert_eq!(hybrid_sum(&gpu, &data, fraction), sum, "{}", fraction);
+    }
+}
+
+#[test]
+fn a_missing_adapter_names_the_searched_backends() {
+    let err = GpuError::NoAdapter {
+        backends: wgpu::Backends::VULKAN | wgpu::Backends::GL,
+        fallback_attempted: true,
+    };
+
+    assert_eq!(
+        err.to_string(),
+        "no adapter found, searched backends: [vulkan, gl], software fallback attempted"
+    );
+}
+
+#[test]
+fn run_in_place_writes_the_kernel_output_back_into_the_bindings() {
+    let Some(gpu) = gpu(Op::Add.shader_file()) else {
+        return;
+    };
+    let data = generate_data(1000, 11);
+    let increment = gpu.create_module("increment", &gen_unary_kernel("x + 1u"));
+
+    let mut bindings = Bindings::initialize_one(data.clone());
+    let stage = Stage::new(UNARY_ENTRY, 1)
+        .dispatch(gpu.dispatch_for(data.len()).unwrap())
+        .module(&increment);
+    let bc = BufCoder::initialize_stages(&gpu, &mut bindings, &[stage]);
+    pollster::block_on(gpu.run_in_place(&bc, &mut bindings));
+
+    let incremented: Vec<u32> = data.iter().map(|&x| x + 1).collect();
+    assert_eq!(bindings.input_output(), incremented);
+}
+
+#[test]
+fn specialize_workgroup_size_rewrites_every_attribute_and_keeps_y_and_z() {
+    let source =
+        "@compute @workgroup_size(256)\nfn a() {}\n\n@compute @workgroup_size(16, 16)\nfn b() {}\n";
+
+    assert_eq!(
+        specialize_workgroup_size(source, 64),
+        "@compute @workgroup_size(64)\nfn a() {}\n\n@compute @workgroup_size(64, 16)\nfn b() {}\n"
+    );
+}
+
+#[test]
+fn pipelines_specialized_to_two_sizes_both_run() {
+    let Some(gpu) = gpu(Op::Sum.shader_file()) else {
+        return;
+    };
+    let data = generate_data(1000, 12);
+
+    for size in [64, 128] {
+        let module = gpu.create_module(
+            "specialized",
+            &specialize_workgroup_size(include_str!("sum_func.wgsl"), size),
+        );
+        let mut bindings = Op::Sum.bindings(&data);
+        let stage = Stage::new(Op::Sum.entry_point(), 2)
+            .dispatch(Dispatch::Workgroups(
+                workgroup_count_for(data.len(), size),
+                1,
+                1,
+            ))
+            .module(&module);
+        let bc = BufCoder::initialize_stages(&gpu, &mut bindings, &[stage]);
+
+        assert_eq!(
+            pollster::block_on(gpu.run::<u32>(&bc)).unwrap(),
+            Op::Sum.run_cpu(&data),
+            "{}",
+            size
+        );
+    }
+}
+
+#[test]
+fn autotune_picks_one_of_the_candidates() {
+    let Some(gpu) = gpu(Op::Sum.shader_file()) else {
+        return;
+    };
+    let data = generate_data(10_000, 13);
+    let candidates = [32, 64, 128, 256];
+
+    let size = gpu.autotune_workgroup_size(Op::Sum.entry_point(), &data, &candidates);
+    assert!(candidates.contains(&size));
+
+    // The winner still computes the sum
+    let module = gpu.create_module(
+        "autotuned",
+        &specialize_workgroup_size(include_str!("sum_func.wgsl"), size),
+    );
+    let mut bindings = Op::Sum.bindings(&data);
+    let stage = Stage::new(Op::Sum.entry_point(), 2)
+        .dispatch(Dispatch::Workgroups(
+            workgroup_count_for(data.len(), size),
+            1,
+            1,
+        ))
+        .module(&module);
+    let bc = BufCoder::initialize_stages(&gpu, &mut bindings, &[stage]);
+    assert_eq!(
+        pollster::block_on(gpu.run::<u32>(&bc)).unwrap(),
+        Op::Sum.run_cpu(&data)
+    );
+}
+
+#[test]
+fn a_second_dispatch_of_the_same_size_reuses_the_pooled_buffers() {
+    let Some(gpu) = gpu(Op::Add.shader_file()) else {
+        return;
+    };
+    let data = generate_data(1000, 14);
+    let dispatch = || {
+        let mut bindings = Op::Add.bindings(&data);
+        let bc =
+            BufCoder::initialize_stages(
+                &gpu,
+                &mut bindings,
+                &[Stage::new(Op::Add.entry_point(), 3)
+                    .dispatch(gpu.dispatch_for(data.len()).unwrap())],
+            );
+        pollster::block_on(gpu.run::<u32>(&bc)).unwrap()
+    };
+
+    assert_eq!(dispatch(), Op::Add.run_cpu(&data));
+    let pooled = gpu.buffer_pool().len();
+    assert!(pooled > 0);
+
+    // Every buffer of the second dispatch comes out of the pool, and goes back into it
+    let before = gpu.buffer_pool().retained_bytes();
+    assert_eq!(dispatch(), Op::Add.run_cpu(&data));
+    assert_eq!(gpu.buffer_pool().len(), pooled);
+    assert_eq!(gpu.buffer_pool().retained_bytes(), before);
+}
+
+#[cfg(feature = "ndarray")]
+#[test]
+fn run_as_array2_reshapes_a_matmul_output() {
+    let Some(gpu) = gpu(Op::Add.shader_file()) else {
+        return;
+    };
+    // c = a * b for a row-major 2 x 3 `a` and 3 x 4 `b`, one invocation per element of c
+    let matmul = gpu.create_module(
+        "matmul",
+        "@group(0) @binding(0) var<storage, read_write> c: array<u32>;
+@group(0) @binding(1) var<storage, read> a: array<u32>;
+@group(0) @binding(2) var<storage, read> b: array<u32>;
+
+@compute @workgroup_size(8)
+fn matmul_call(@builtin(local_invocation_index) idx: u32) {
+  let row = idx / 4u;
+  let col = idx % 4u;
+  var sum = 0u;
+  for (var k = 0u; k < 3u; k = k + 1u) {
+    sum = sum + a[row * 3u + k] * b[k * 4u + col];
+  }
+  c[idx] = sum;
+}
+",
+    );
+    let a: Vec<u32> = (1..=6).collect();
+    let b: Vec<u32> = (1..=12).collect();
+    let run = |shape| {
+        let mut bindings = Bindings::initialize_three(vec![0; 8], a.clone(), b.clone());
+        let stage = Stage::new("matmul_call", 3)
+            .dispatch(Dispatch::Workgroups(1, 1, 1))
+            .module(&matmul);
+        let bc = BufCoder::initialize_stages(&gpu, &mut bindings, &[stage]);
+        pollster::block_on(gpu.run_as_array2(&bc, shape))
+    };
+
+    let c = run((2, 4)).unwrap();
+    // Row 1 of a is [4, 5, 6], column 2 of b is [3, 7, 11]
+    assert_eq!(c[[1, 2]], 4 * 3 + 5 * 7 + 6 * 11);
+    assert!(matches!(
+        run((3, 3)),
+        Err(GpuError::ShapeMismatch {
+            shape: (3, 3),
+            len: 8
+        })
+    ));
+}
+
+#[test]
+fn wait_idle_returns_once_the_submitted_work_is_done() {
+    let Some(gpu) = gpu(Op::Sum.shader_file()) else {
+        return;
+    };
+    let data = generate_data(100_000, 15);
+    let mut bindings = Op::Sum.bindings(&data);
+    let stage =
+        Stage::new(Op::Sum.entry_point(), 2).dispatch(gpu.dispatch_for(data.len()).unwrap());
+    let bc = BufCoder::initialize_stages(&gpu, &mut bindings, &[stage]);
+
+    let done = Arc::new(AtomicBool::new(false));
+    let flag = done.clone();
+    gpu.queue
+        .on_submitted_work_done(move || flag.store(true, Ordering::SeqCst));
+    gpu.wait_idle();
+
+    assert!(done.load(Ordering::SeqCst));
+    assert_eq!(
+        pollster::block_on(gpu.run::<u32>(&bc)).unwrap(),
+        Op::Sum.run_cpu(&data)
+    );
+}
+
+#[test]
+fn atomic_max_and_min_match_the_cpu() {
+    let Some(gpu) = gpu(Op::Add.shader_file()) else {
+        return;
+    };
+    let data = generate_data(10_000, 16);
+
+    assert_eq!(gpu_atomic_max(&gpu, &data), data.iter().copied().max());
+    assert_eq!(gpu_atomic_min(&gpu, &data), data.iter().copied().min());
+    assert_eq!(gpu_atomic_max(&gpu, &[]), None);
+}
+
+#[test]
+fn measure_gflops_is_positive_for_the_fma_loop() {
+    let Some(gpu) = gpu("src/flops_func.wgsl") else {
+        return;
+    };
+
+    let gflops = measure_gflops(&gpu, "fmaLoop_call", 512, 100_000);
+    assert!(gflops > 0.0 && gflops.is_finite(), "{}", gflops);
+}
+
+#[test]
+fn texture_path_increments_every_texel() {
+    let Some(gpu) = gpu("src/texture_func.wgsl") else {
+        return;
+    };
+    // 30 texels in rows of 7, so the last row is padded
+    let data = generate_data(30, 17);
+
+    let result = pollster::block_on(gpu.run_texture("textureIncrement_call", &data, 7));
+    let incremented: Vec<u32> = data.iter().map(|&x| x + 1).collect();
+    assert_eq!(result, Some(incremented));
+    assert_eq!(
+        pollster::block_on(gpu.run_texture("textureIncrement_call", &[], 7)),
+        None
+    );
+}
+
+#[test]
+fn gpu_stats_match_the_cpu_on_random_data() {
+    let Some(gpu) = gpu(Op::Add.shader_file()) else {
+        return;
+    };
+    // Full range values, so the low word of the sum wraps many times
+    let mut rng = StdRng::seed_from_u64(18);
+    let data: Vec<u32> = (0..10_000).map(|_| rng.gen()).collect();
+
+    assert_eq!(gpu_stats(&gpu, &data), cpu_stats(&data));
+    assert_eq!(gpu_stats(&gpu, &[]), None);
+}
+
+#[test]
+fn retry_recovers_from_one_failed_attempt() {
+    let policy = RetryPolicy {
+        retries: 2,
+        backoff: Duration::from_millis(10),
+    };
+    let mut attempts = 0;
+    let mut pauses = Vec::new();
+
+    let result = pollster::block_on(policy.run_with_sleep(
+        || {
+            attempts += 1;
+            std::future::ready((attempts > 1).then_some(attempts))
+        },
+        |pause| {
+            pauses.push(pause);
+            std::future::ready(())
+        },
+    ));
+
+    assert_eq!(result, Some(2));
+    assert_eq!(pauses, [Duration::from_millis(10)]);
+}
+
+#[test]
+fn retry_gives_up_after_the_retries_with_doubling_pauses() {
+    let policy = RetryPolicy {
+        retries: 2,
+        backoff: Duration::from_millis(10),
+    };
+    let mut attempts = 0;
+    let mut pauses = Vec::new();
+
+    let result: Option<()> = pollster::block_on(policy.run_with_sleep(
+        || {
+            attempts += 1;
+            std::future::ready(None)
+        },
+        |pause| {
+            pauses.push(pause);
+            std::future::ready(())
+        },
+    ));
+
+    assert_eq!(result, None);
+    assert_eq!(attempts, 3);
+    assert_eq!(
+        pauses,
+        [Duration::from_millis(10), Duration::from_millis(20)]
+    );
+}
+
+#[test]
+fn run_to_writer_writes_the_bytes_of_the_result() {
+    let Some(gpu) = gpu(Op::Add.shader_file()) else {
+        return;
+    };
+    let data = generate_data(1000, 19);
+    let mut bindings = Op::Add.bindings(&data);
+    let stage =
+        Stage::new(Op::Add.entry_point(), 3).dispatch(gpu.dispatch_for(data.len()).unwrap());
+    let bc = BufCoder::initialize_stages(&gpu, &mut bindings, &[stage]);
+
+    let mut bytes = Vec::new();
+    pollster::block_on(gpu.run_to_writer(&bc, &mut bytes)).unwrap();
+    assert_eq!(
+        bytes,
+        bytemuck::cast_slice::<u32, u8>(&Op::Add.run_cpu(&data))
+    );
+}
+
+#[cfg(target_pointer_width = "64")]
+#[test]
+fn lengths_past_u32_are_rejected_without_allocating_them() {
+    let len = u32::MAX as usize + 1;
+
+    assert!(matches!(check_len(len), Err(GpuError::InputTooLong { len: l }) if l == len));
+    assert_eq!(check_len(u32::MAX as usize).unwrap(), u32::MAX);
+}
+
+#[test]
+fn thresholded_optimized_sum_matches_sum_vec_around_the_base_case() {
+    let data = generate_data(1000, 20);
+
+    for len in [
+        1,
+        2,
+        OPTIMIZED_SUM_BASE - 1,
+        OPTIMIZED_SUM_BASE,
+        OPTIMIZED_SUM_BASE + 1,
+        1000,
+    ] {
+        assert_eq!(
+            optimized_sum_vec(&data, 0, len - 1),
+            sum_vec(&data, len),
+            "{}",
+            len
+        );
+        assert_eq!(recursive_sum_vec(&data, 0, len - 1), sum_vec(&data, len));
+    }
+}
+
+#[test]
+fn can_run_names_the_limit_an_oversized_request_exceeds() {
+    let Some(gpu) = gpu(Op::Add.shader_file()) else {
+        return;
+    };
+    let limits = gpu.device.limits();
+    let too_long = limits.max_storage_buffer_binding_size as usize / 4 + 1;
+    let too_many = limits.max_storage_buffers_per_shader_stage as usize + 1;
+
+    assert!(gpu.can_run(1000, 3).is_ok());
+    assert!(matches!(
+        gpu.can_run(too_long, 3),
+        Err(GpuError::LimitExceeded {
+            limit: "max_storage_buffer_binding_size",
+            ..
+        })
+    ));
+    assert!(matches!(
+        gpu.can_run(1000, too_many),
+        Err(GpuError::LimitExceeded {
+            limit: "max_storage_buffers_per_shader_stage",
+            ..
+        })
+    ));
+}
+
+#[test]
+fn gpu_counts_match_the_cpu() {
+    let Some(gpu) = gpu(Op::Add.shader_file()) else {
+        return;
+    };
+    let data = generate_data(10_000, 21);
+
+    assert_eq!(
+        gpu_count(&gpu, &data, "countEven_call"),
+        cpu_count(&data, |x| x % 2 == 0)
+    );
+    assert_eq!(
+        gpu_count(&gpu, &data, "countOdd_call"),
+        cpu_count(&data, |x| x % 2 == 1)
+    );
+}
+
+#[test]
+fn f32_output_is_read_back_as_f32() {
+    let Some(gpu) = gpu("src/saxpy_func.wgsl") else {
+        return;
+    };
+    let mut bindings = Bindings::initialize_one(Vec::new());
+    bindings.set_binding(0, &[0.5f32, 0.5, 0.5]);
+    bindings.set_binding(1, &[1.0f32, 2.0, 3.0]);
+    let stage = Stage::new("saxpy_call", 2).params([2.0f32.to_bits(), 0, 0, 0]);
+    let bc = BufCoder::initialize_stages(&gpu, &mut bindings, &[stage]);
+
+    // Small integers and halves are exact in f32, fused or not
+    assert_eq!(
+        pollster::block_on(gpu.run::<f32>(&bc)).unwrap(),
+        [2.5, 4.5, 6.5]
+    );
+    assert!(matches!(
+        pollster::block_on(gpu.run::<u64>(&bc)),
+        Err(GpuError::MisalignedReadback {
+            bytes: 12,
+            elem_size: 8
+        })
+    ));
+}
+
+#[test]
+fn the_crossover_of_the_sum_separates_slower_and_faster_gpu_runs() {
+    // Known timings: the GPU wins from 1234 elements on
+    let mut tried = Vec::new();
+    let crossover = search_crossover(CROSSOVER_MAX_LEN, |len| {
+        tried.push(len);
+        len >= 1234
+    });
+    assert_eq!(crossover, Some(1234));
+    assert!(tried
+        .iter()
+        .all(|len| (1..=CROSSOVER_MAX_LEN).contains(len)));
+    assert_eq!(search_crossover(CROSSOVER_MAX_LEN, |_| true), Some(1));
+    assert_eq!(search_crossover(CROSSOVER_MAX_LEN, |_| false), None);
+
+    // Real timings are too noisy to check more than the range of the result
+    let Some(gpu) = gpu(Op::Sum.shader_file()) else {
+        return;
+    };
+    if let Some(size) = crossover_size(&gpu, Op::Sum) {
+        assert!((1..=CROSSOVER_MAX_LEN).contains(&size));
+    }
+}
+
+#[test]
+fn set_input_between_dispatches_uploads_the_new_data() {
+    let Some(gpu) = gpu(Op::Add.shader_file()) else {
+        return;
+    };
+    let increment = gpu.create_module("increment", &gen_unary_kernel("x + 1u"));
+    let first = generate_data(1000, 22);
+    let second = generate_data(1000, 23);
+    let run = |bindings: &mut Bindings| {
+        let stage = Stage::new(UNARY_ENTRY, 1)
+            .dispatch(gpu.dispatch_for(1000).unwrap())
+            .module(&increment);
+        let bc = BufCoder::initialize_stages(&gpu, bindings, &[stage]);
+        pollster::block_on(gpu.run::<u32>(&bc)).unwrap()
+    };
+    let incremented = |data: &[u32]| data.iter().map(|&x| x + 1).collect::<Vec<u32>>();
+
+    let mut bindings = Bindings::initialize_one(first.clone());
+    assert_eq!(run(&mut bindings), incremented(&first));
+
+    let allocation = bindings.input_output().as_ptr();
+    bindings.set_input(&second);
+    assert_eq!(bindings.input_output().as_ptr(), allocation);
+    assert_eq!(run(&mut bindings), incremented(&second));
+}
+
+// The level of an event of `trace_resource!` and its fields by name.
+#[cfg(feature = "trace-resources")]
+type CapturedEvent = (tracing::Level, HashMap<&'static str, String>);
+
+#[cfg(feature = "trace-resources")]
+#[derive(Clone, Default)]
+struct CapturedEvents(Arc<Mutex<Vec<CapturedEvent>>>);
+
+#[cfg(feature = "trace-resources")]
+impl CapturedEvents {
+    fn count(&self, message: &str, kind: &str) -> usize {
+        self.0
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(_, fields)| {
+                fields.get("message").map(String::as_str) == Some(message)
+                    && fields.get("kind").map(String::as_str) == Some(kind)
+            })
+            .count()
+    }
+
+    fn labels(&self, message: &str, kind: &str) -> Vec<String> {
+        self.0
+            .lock()
+            .unwrap()
+            .iter()
+            .filter(|(_, fields)| {
+                fields.get("message").map(String::as_str) == Some(message)
+                    && fields.get("kind").map(String::as_str) == Some(kind)
+            })
+            .filter_map(|(_, fields)| fields.get("label").cloned())
+            .collect()
+    }
+}
+
+#[cfg(feature = "trace-resources")]
+impl tracing::Subscriber for CapturedEvents {
+    fn enabled(&self, metadata: &tracing::Metadata<'_>) -> bool {
+        metadata.target() == "wgsl_example::resources"
+    }
+
+    fn new_span(&self, _: &tracing::span::Attributes<'_>) -> tracing::span::Id {
+        tracing::span::Id::from_u64(1)
+    }
+
+    fn record(&self, _: &tracing::span::Id, _: &tracing::span::Record<'_>) {}
+
+    fn record_follows_from(&self, _: &tracing::span::Id, _: &tracing::span::Id) {}
+
+    fn event(&self, event: &tracing::Event<'_>) {
+        struct Fields(HashMap<&'static str, String>);
+        impl tracing::field::Visit for Fields {
+            fn record_debug(&mut self, field: &tracing::field::Field, value: &dyn Debug) {
+                self.0.insert(field.name(), format!("{:?}", value));
+            }
+
+            fn record_str(&mut self, field: &tracing::field::Field, value: &str) {
+                self.0.insert(field.name(), value.to_string());
+            }
+        }
+
+        let mut fields = Fields(HashMap::new());
+        event.record(&mut fields);
+        let level = *event.metadata().level();
+        self.0.lock().unwrap().push((level, fields.0));
+    }
+
+    fn enter(&self, _: &tracing::span::Id) {}
+
+    fn exit(&self, _: &tracing::span::Id) {}
+}
+
+#[cfg(feature = "trace-resources")]
+#[test]
+fn one_dispatch_traces_the_buffers_it_creates() {
+    let Some(gpu) = gpu("src/grid_func.wgsl") else {
+        return;
+    };
+    let events = CapturedEvents::default();
+    tracing::subscriber::with_default(events.clone(), || {
+        let mut bindings = Bindings::initialize_one(vec![0; 200]);
+        let bc = BufCoder::initialize_stages(
+            &gpu,
+            &mut bindings,
+            &[Stage::new("markCells2d_call", 1).dispatch_2d(20, 10)],
+        );
+        pollster::block_on(gpu.run::<u32>(&bc)).unwrap();
+    });
+
+    // The four bindings and the params, with the staging and timestamp buffers if any
+    let staging = usize::from(gpu.readback_path() == ReadbackPath::Staging);
+    let timestamps = if gpu
+        .device
+        .features()
+        .contains(wgpu::Features::TIMESTAMP_QUERY)
+    {
+        2
+    } else {
+        0
+    };
+    assert_eq!(events.count("created", "buffer"), 5 + staging + timestamps);
+    assert_eq!(events.count("created", "bind group"), 2);
+    assert_eq!(events.count("dropped", "bind group"), 2);
+    assert_eq!(events.count("created", "pipeline"), 1);
+}
+
+#[test]
+fn a_cleared_histogram_starts_from_zero_every_run() {
+    let Some(gpu) = gpu(Op::Add.shader_file()) else {
+        return;
+    };
+    let histogram = gpu.create_module(
+        "histogram",
+        "@group(0) @binding(0) var<storage, read_write> bins: array<atomic<u32>>;
+@group(0) @binding(1) var<storage, read> input: array<u32>;
+
+@compute @workgroup_size(256)
+fn histogram_call(@builtin(global_invocation_id) global_id: vec3u) {
+  if global_id.x < arrayLength(&input) {
+    atomicAdd(&bins[input[global_id.x] % 4u], 1u);
+  }
+}
+",
+    );
+    let data = generate_data(1000, 24);
+    let mut expected = vec![0; 4];
+    for x in &data {
+        expected[(x % 4) as usize] += 1;
+    }
+
+    // The bins start as stale host data, and the second run reuses the buffers of the first
+    let mut bindings = Bindings::initialize_two(vec![7; 4], data);
+    for _ in 0..2 {
+        let stage = Stage::new("histogram_call", 2)
+            .dispatch(gpu.dispatch_for(1000).unwrap())
+            .module(&histogram)
+            .clear_before_dispatch(&[0]);
+        let bc = BufCoder::initialize_stages(&gpu, &mut bindings, &[stage]);
+        assert_eq!(pollster::block_on(gpu.run::<u32>(&bc)).unwrap(), expected);
+    }
+}
+
+#[test]
+fn references_round_trip_as_little_endian_files() {
+    let dir = std::env::temp_dir();
+    let result = generate_data(100, 25);
+    let binary = dir.join(format!("wgsl-example-reference-{}.bin", std::process::id()));
+    let bytes: Vec<u8> = result.iter().flat_map(|x| x.to_le_bytes()).collect();
+    std::fs::write(&binary, bytes).unwrap();
+    assert!(compare_against_reference(&result, &binary).is_ok());
+
+    let mut wrong = result.clone();
+    wrong[10] += 1;
+    wrong[20] += 5;
+    match compare_against_reference(&wrong, &binary) {
+        Err(Mismatch::Values {
+            first,
+            largest,
+            count,
+        }) => {
+            assert_eq!((first.index, largest.index, count), (10, 20, 2));
+        }
+        other => panic!("expected differing values, got {:?}", other),
+    }
+
+    let floats = [0.5f32, -1.25, 3.0e7];
+    let bytes: Vec<u8> = floats.iter().flat_map(|x| x.to_le_bytes()).collect();
+    std::fs::write(&binary, bytes).unwrap();
+    let tolerance = Tolerance::new(1e-6, 0.0);
+    assert!(compare_against_reference_f32(&floats, &binary, tolerance).is_ok());
+    assert!(compare_against_reference_f32(&[0.5, -1.25, 3.1e7], &binary, tolerance).is_err());
+    std::fs::remove_file(&binary).unwrap();
+
+    let csv = dir.join(format!("wgsl-example-reference-{}.csv", std::process::id()));
+    std::fs::write(&csv, "1,2,3\n4 5").unwrap();
+    assert!(compare_against_reference(&[1, 2, 3, 4, 5], &csv).is_ok());
+    assert!(matches!(
+        compare_against_reference(&[1, 2, 3], &csv),
+        Err(Mismatch::Length {
+            actual: 3,
+            expected: 5
+        })
+    ));
+    std::fs::remove_file(&csv).unwrap();
+}
+
+#[test]
+fn the_tree_reduction_agrees_with_the_cpu() {
+    let Some(gpu) = gpu(Op::Add.shader_file()) else {
+        return;
+    };
+    for len in [1, 255, 256, 257, 100_000] {
+        let data = generate_data(len, 26);
+        assert_eq!(
+            gpu_reduce_sum(&gpu, &data),
+            sum_vec(&data, len),
+            "{} elements",
+            len
+        );
+    }
+}
+
+#[test]
+fn transfer_bandwidths_are_positive_and_finite() {
+    let Some(gpu) = gpu(Op::Add.shader_file()) else {
+        return;
+    };
+    let (upload, download) = measure_transfer_bandwidth(&gpu, 1 << 20);
+    assert!(upload.is_finite() && upload > 0.0, "upload {} GB/s", upload);
+    assert!(
+        download.is_finite() && download > 0.0,
+        "download {} GB/s",
+        download
+    );
+}
+
+#[test]
+fn grids_past_whole_workgroups_mark_every_cell_once() {
+    let Some(gpu) = gpu("src/grid_func.wgsl") else {
+        return;
+    };
+    let mark = |stage: Stage, cells: usize| {
+        let mut bindings = Bindings::initialize_one(vec![0; cells]);
+        let bc = BufCoder::initialize_stages(&gpu, &mut bindings, &[stage]);
+        pollster::block_on(gpu.run::<u32>(&bc)).unwrap()
+    };
+    let every_cell = |cells: usize| (1..=cells as u32).collect::<Vec<u32>>();
+
+    assert_eq!(
+        mark(
+            Stage::new("markCells2d_call", 1).dispatch_2d(17, 33),
+            17 * 33
+        ),
+        every_cell(17 * 33)
+    );
+    assert_eq!(
+        mark(
+            Stage::new("markCells3d_call", 1).dispatch_3d(9, 7, 5),
+            9 * 7 * 5
+        ),
+        every_cell(9 * 7 * 5)
+    );
+}
+
+#[test]
+fn run_timed_returns_the_result_and_a_positive_time() {
+    let Some(gpu) = gpu(Op::Add.shader_file()) else {
+        return;
+    };
+    let a = generate_data(1000, 27);
+    let b = generate_data(1000, 28);
+    let mut bindings = Bindings::initialize_three(vec![0; a.len()], a.clone(), b.clone());
+    let bc = BufCoder::initialize_stages(
+        &gpu,
+        &mut bindings,
+        &[Stage::new("vectorAddition_call", 3).dispatch(gpu.dispatch_for(a.len()).unwrap())],
+    );
+
+    let (result, time) = pollster::block_on(gpu.run_timed(&bc)).unwrap();
+    assert_eq!(result, add_two_vec(&a, &b, a.len()));
+    if let Some(time) = time {
+        assert!(time > Duration::ZERO);
+    }
+}
+
+#[test]
+fn batch_with_generates_inputs_for_every_iteration() {
+    let mut calls = Vec::new();
+    batch_with(
+        |i| {
+            calls.push(i);
+            (generate_data(10, i as u64), generate_data(10, i as u64 + 1))
+        },
+        5,
+    );
+    assert_eq!(calls, [0, 1, 2, 3, 4]);
+}
+
+#[test]
+fn a_readback_ring_returns_every_result_of_three_rounds() {
+    let Some(gpu) = gpu(Op::Add.shader_file()) else {
+        return;
+    };
+    let increment = gpu.create_module("increment", &gen_unary_kernel("x + 1u"));
+    let slots = 2;
+    let mut ring = ReadbackRing::new(&gpu, slots, 1000 * 4);
+
+    // Every slot is read before the ring comes back to it
+    let mut pending = std::collections::VecDeque::new();
+    for i in 0..3 * slots as u64 {
+        let data = generate_data(1000, 29 + i);
+        let mut bindings = Bindings::initialize_one(data.clone());
+        let stage = Stage::new(UNARY_ENTRY, 1)
+            .dispatch(gpu.dispatch_for(1000).unwrap())
+            .module(&increment);
+        let bc = BufCoder::initialize_stages(&gpu, &mut bindings, &[stage]);
+        let slot = ring.enqueue(&gpu, &bc).unwrap();
+        pending.push_back((slot, data.iter().map(|&x| x + 1).collect::<Vec<u32>>()));
+
+        if pending.len() == slots {
+            let (slot, expected) = pending.pop_front().unwrap();
+            assert_eq!(pollster::block_on(ring.read(&gpu, slot, 1000)), expected);
+        }
+    }
+    for (slot, expected) in pending {
+        assert_eq!(pollster::block_on(ring.read(&gpu, slot, 1000)), expected);
+    }
+}
+
+#[test]
+fn compute_only_dispatches_neither_stage_nor_map_the_result() {
+    let Some(gpu) = gpu(Op::Add.shader_file()) else {
+        return;
+    };
+    let len = 1 << 20;
+    let a = generate_data(len, 35);
+    let b = generate_data(len, 36);
+    let workgroups = gpu
+        .plan_dispatch(len.div_ceil(256) as u64)
+        .unwrap()
+        .workgroups;
+    let compute_only = || {
+        let mut bindings = Bindings::initialize_three(vec![0; len], a.clone(), b.clone());
+        dispatch_compute_only(&gpu, &mut bindings, "vectorAddition_call", 3, workgroups);
+    };
+
+    #[cfg(feature = "trace-resources")]
+    {
+        let staged = |events: &CapturedEvents| {
+            events
+                .labels("created", "buffer")
+                .iter()
+                .filter(|label| *label == "Staging Buffer")
+                .count()
+        };
+        let events = CapturedEvents::default();
+        tracing::subscriber::with_default(events.clone(), compute_only);
+        assert_eq!(staged(&events), 0);
+        assert_eq!(events.count("reused", "buffer"), 0);
+        // Nothing was copied back to be read, so dropping it isn't a forgotten result
+        assert!(events
+            .0
+            .lock()
+            .unwrap()
+            .iter()
+            .all(|(level, _)| *level != tracing::Level::WARN));
+
+        // A full run of the same kernel stages its result, where the device needs it
+        let events = CapturedEvents::default();
+        tracing::subscriber::with_default(events.clone(), || {
+            let mut bindings = Bindings::initialize_three(vec![0; len], a.clone(), b.clone());
+            let stage =
+                Stage::new("vectorAddition_call", 3).dispatch(gpu.dispatch_for(len).unwrap());
+            let bc = BufCoder::initialize_stages(&gpu, &mut bindings, &[stage]);
+            pollster::block_on(gpu.run::<u32>(&bc)).unwrap();
+        });
+        let staging = usize::from(gpu.readback_path() == ReadbackPath::Staging);
+        assert_eq!(staged(&events), staging);
+    }
+    #[cfg(not(feature = "trace-resources"))]
+    compute_only();
+}
+
+#[cfg(feature = "simd")]
+#[test]
+fn simd_sum_vec_agrees_with_sum_vec_and_wraps() {
+    for len in 0..=33 {
+        let data = generate_data(len, 37);
+        assert_eq!(simd_sum_vec(&data), sum_vec(&data, len), "{} elements", len);
+    }
+
+    // Overflows in the lanes, in the tail and when they are added together
+    let data = vec![u32::MAX - 1; 19];
+    assert_eq!(simd_sum_vec(&data), sum_vec(&data, data.len()));
+}
+
+#[test]
+fn a_compiled_kernel_dispatches_twice_without_recompiling() {
+    let Some(gpu) = gpu(Op::Add.shader_file()) else {
+        return;
+    };
+    let kernel = gpu.compile_kernel("vectorAddition_call").unwrap();
+    assert_eq!(kernel.workgroup_size, [256, 1, 1]);
+    let dispatch_twice = || {
+        for seed in [38, 39] {
+            let a = generate_data(1000, seed);
+            let b = generate_data(1000, seed + 10);
+            let mut bindings = Bindings::initialize_three(vec![0; a.len()], a.clone(), b.clone());
+            let stage = Stage::from_kernel(&kernel, 3).dispatch(gpu.dispatch_for(1000).unwrap());
+            let bc = BufCoder::initialize_stages(&gpu, &mut bindings, &[stage]);
+            assert_eq!(
+                pollster::block_on(gpu.run::<u32>(&bc)).unwrap(),
+                add_two_vec(&a, &b, a.len())
+            );
+        }
+    };
+
+    #[cfg(feature = "trace-resources")]
+    {
+        let events = CapturedEvents::default();
+        tracing::subscriber::with_default(events.clone(), dispatch_twice);
+        assert_eq!(events.count("created", "pipeline"), 0);
+    }
+    #[cfg(not(feature = "trace-resources"))]
+    dispatch_twice();
+}
+
+#[test]
+fn a_wrong_binding_count_is_a_mismatch() {
+    let Some(gpu) = gpu(Op::Add.shader_file()) else {
+        return;
+    };
+    let mut bindings = Bindings::initialize_three(vec![0; 10], vec![1; 10], vec![2; 10]);
+    match BufCoder::try_initialize(&gpu, &mut bindings, "vectorAddition_call", 2) {
+        Err(GpuError::BindingCountMismatch {
+            entry,
+            expected,
+            given,
+        }) => assert_eq!(
+            (entry.as_str(), expected, given),
+            ("vectorAddition_call", 3, 2)
+        ),
+        Err(err) => panic!("expected a binding count mismatch, got {}", err),
+        Ok(_) => panic!("expected a binding count mismatch"),
+    }
+    BufCoder::try_initialize(&gpu, &mut bindings, "vectorAddition_call", 3)
+        .unwrap()
+        .discard();
+}
+
+#[cfg(feature = "test-util")]
+#[test]
+fn assert_cpu_gpu_agree_names_where_a_broken_kernel_differs() {
+    let Some(gpu) = gpu(Op::Add.shader_file()) else {
+        return;
+    };
+    let data = generate_data(1000, 40);
+    assert_cpu_gpu_agree(&gpu, Op::Add, &data);
+
+    // `vectorAddition_call` of `vec_func.wgsl`, off by one at index 5
+    let broken =
+        std::env::temp_dir().join(format!("wgsl-example-broken-{}.wgsl", std::process::id()));
+    std::fs::write(
+        &broken,
+        "@group(0) @binding(0) var<storage, read_write> output: array<u32>;
+@group(0) @binding(1) var<storage, read> input_a: array<u32>;
+@group(0) @binding(2) var<storage, read> input_b: array<u32>;
+
+@compute @workgroup_size(256)
+fn vectorAddition_call(
+  @builtin(global_invocation_id) global_id: vec3u,
+  @builtin(num_workgroups) num_workgroups: vec3u,
+) {
+  let idx = global_id.x + (global_id.y + global_id.z * num_workgroups.y) * num_workgroups.x * 256u;
+  if idx < arrayLength(&output) {
+    output[idx] = input_a[idx] + input_b[idx] + select(0u, 1u, idx == 5u);
+  }
+}
+",
+    )
+    .unwrap();
+    let broken_gpu = pollster::block_on(GpuConsts::initialaze(broken.to_str().unwrap())).unwrap();
+    std::fs::remove_file(&broken).unwrap();
+
+    let panic = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+        assert_cpu_gpu_agree(&broken_gpu, Op::Add, &data)
+    }))
+    .unwrap_err();
+    let message = panic.downcast_ref::<String>().unwrap();
+    assert!(message.contains("differs at index 5"), "{}", message);
+    assert!(message.contains("input of 1000"), "{}", message);
+}
+
+#[test]
+fn the_checked_gpu_add_flags_exactly_the_overflowing_inputs() {
+    let Some(gpu) = gpu(Op::Add.shader_file()) else {
+        return;
+    };
+    let a = generate_data(1000, 41);
+    let b = generate_data(1000, 42);
+    let (result, overflowed) = gpu_checked_add(&gpu, &a, &b);
+    assert_eq!(result, add_two_vec(&a, &b, a.len()));
+    assert!(!overflowed);
+    assert!(checked_add_two_vec(&a, &b, a.len()).is_some());
+
+    let mut high = a.clone();
+    high[700] = u32::MAX;
+    let (result, overflowed) = gpu_checked_add(&gpu, &high, &[1; 1000]);
+    assert_eq!(result, add_two_vec(&high, &[1; 1000], high.len()));
+    assert!(overflowed);
+    assert!(checked_add_two_vec(&high, &[1; 1000], high.len()).is_none());
+}
+
+#[test]
+fn two_contexts_share_one_instance() {
+    let Some(first) = gpu(Op::Add.shader_file()) else {
+        return;
+    };
+    let options = GpuOptions {
+        instance: Some(first.instance().clone()),
+        ..Default::default()
+    };
+    let second =
+        pollster::block_on(GpuConsts::initialaze_with(Op::Sum.shader_file(), &options)).unwrap();
+
+    assert!(Arc::ptr_eq(first.instance(), second.instance()));
+    let data = generate_data(1000, 43);
+    assert_eq!(Op::Add.run_gpu(&first, &data), Op::Add.run_cpu(&data));
+    assert_eq!(Op::Sum.run_gpu(&second, &data), Op::Sum.run_cpu(&data));
+}
+
+#[test]
+fn a_module_kernel_guarded_by_its_lengths_handles_the_tail() {
+    let Some(gpu) = gpu(Op::Add.shader_file()) else {
+        return;
+    };
+    let module = gpu.create_module("lengths", include_str!("length_func.wgsl"));
+    let len = 1000;
+    let data = generate_data(len, 44);
+    let mut bindings = Bindings::initialize_one(data.clone());
+    let stage = Stage::new("incrementGuarded_call", 1)
+        .dispatch(Dispatch::Workgroups(len.div_ceil(256) as u32, 1, 1))
+        .module(&module);
+    let bc = BufCoder::initialize_stages(&gpu, &mut bindings, &[stage]);
+
+    let result = pollster::block_on(gpu.run::<u32>(&bc)).unwrap();
+    assert_eq!(result.len(), len);
+    assert_eq!(result, data.iter().map(|&x| x + 1).collect::<Vec<u32>>());
+}
+
+#[test]
+fn batched_sums_agree_with_the_sum_of_every_vector() {
+    let Some(gpu) = gpu(Op::Add.shader_file()) else {
+        return;
+    };
+    let batch: Vec<Vec<u32>> = [0, 1, 255, 256, 257, 3000]
+        .iter()
+        .enumerate()
+        .map(|(i, &len)| generate_data(len, 45 + i as u64))
+        .collect();
+    let sums: Vec<u32> = batch.iter().map(|v| sum_vec(v, v.len())).collect();
+    assert_eq!(gpu_batched_sum(&gpu, &batch).unwrap(), sums);
+
+    // More vectors than one row of workgroups along x holds
+    let max = gpu.device.limits().max_compute_workgroups_per_dimension as usize;
+    let batch: Vec<Vec<u32>> = (0..max + 2).map(|i| vec![i as u32, 1]).collect();
+    let sums: Vec<u32> = (0..max + 2).map(|i| i as u32 + 1).collect();
+    assert_eq!(gpu_batched_sum(&gpu, &batch).unwrap(), sums);
+}
+
+#[test]
+fn a_generator_kernel_fills_its_output_without_an_input() {
+    let Some(gpu) = gpu(Op::Add.shader_file()) else {
+        return;
+    };
+    let len = 1000;
+    let squares: Vec<u32> = (0..len as u32).map(|i| i.wrapping_mul(i)).collect();
+    assert_eq!(gpu_squares(&gpu, len), squares);
+    assert!(gpu_squares(&gpu, 0).is_empty());
+}
+
+#[test]
+fn launch_latency_is_positive_and_below_a_second() {
+    let Some(gpu) = gpu(Op::Add.shader_file()) else {
+        return;
+    };
+    let latency = measure_launch_latency(&gpu);
+    assert!(latency > Duration::ZERO);
+    assert!(latency < Duration::from_secs(1), "{:?}", latency);
+}
+
+#[cfg(feature = "f16")]
+#[test]
+fn f16_addition_agrees_with_f32_within_f16_tolerance() {
+    let Some(gpu) = gpu(Op::Add.shader_file()) else {
+        return;
+    };
+    // An odd length, so the last word is half padding. Quarters below 512 are exact in
+    // `f16`, so are the inputs and the sums here
+    let a: Vec<f32> = (0..1001).map(|i| i as f32 * 0.25 - 100.0).collect();
+    let b: Vec<f32> = (0..1001).map(|i| (i % 17) as f32 * 1.5).collect();
+    let result = gpu_add_two_vec_f16(&gpu, &a, &b);
+    assert!(Tolerance::F16.all_close(&result, &add_two_vec_f32(&a, &b)));
+}
+
+#[test]
+fn window_sums_agree_with_the_cpu_at_every_window_length() {
+    // Longer than the input, every window is partial
+    let short = generate_data(100, 52);
+    assert_eq!(window_sum_vec(&short, 1000), window_sum_vec(&short, 100));
+    assert_eq!(window_sum_vec(&short, 0), vec![0; 100]);
+
+    let Some(gpu) = gpu(Op::Add.shader_file()) else {
+        return;
+    };
+    let data = generate_data(1000, 51);
+    for window in [0, 1, 3, 256, WINDOW_SUM_MAX] {
+        assert_eq!(
+            gpu_window_sum(&gpu, &data, window).unwrap(),
+            window_sum_vec(&data, window),
+            "window {}",
+            window
+        );
+    }
+    assert_eq!(
+        gpu_window_sum(&gpu, &short, 1000).unwrap(),
+        window_sum_vec(&short, 1000)
+    );
+    assert!(matches!(
+        gpu_window_sum(&gpu, &data, WINDOW_SUM_MAX + 1),
+        Err(GpuError::LimitExceeded {
+            limit: "window",
+            requested,
+            max,
+        }) if requested == WINDOW_SUM_MAX as u64 + 1 && max == WINDOW_SUM_MAX as u64
+    ));
+}
+
+#[test]
+fn a_readback_that_isnt_whole_elements_is_misaligned() {
+    // Mapped ranges are aligned to their elements, and so are the bytes of `words`
+    let words = [1u32, 2];
+    let bytes: &[u8] = bytemuck::cast_slice(&words);
+    assert!(matches!(
+        cast_readback::<u32>(&bytes[..6]),
+        Err(GpuError::MisalignedReadback {
+            bytes: 6,
+            elem_size: 4
+        })
+    ));
+    assert_eq!(cast_readback::<u32>(bytes).unwrap(), words);
+}
+
+#[test]
+fn concurrent_jobs_all_return_their_results() {
+    let Some(gpu) = gpu(Op::Add.shader_file()) else {
+        return;
+    };
+    let tree_sum = gpu.create_module("tree sum", include_str!("tree_sum_func.wgsl"));
+    let double = gpu.create_module("double", &gen_unary_kernel("x * 2u"));
+    let a = generate_data(1000, 53);
+    let b = generate_data(1000, 54);
+    let dispatch = gpu.dispatch_for(a.len()).unwrap();
+    let jobs = vec![
+        Job {
+            id: 0,
+            bindings: Bindings::initialize_three(vec![0; a.len()], a.clone(), b.clone()),
+            stages: vec![Stage::new("vectorAddition_call", 3).dispatch(dispatch)],
+        },
+        Job {
+            id: 1,
+            bindings: Bindings::initialize_two(vec![0], a.clone()),
+            stages: vec![Stage::new("treeSum_call", 2)
+                .dispatch(dispatch)
+                .module(&tree_sum)],
+        },
+        Job {
+            id: 2,
+            bindings: Bindings::initialize_one(b.clone()),
+            stages: vec![Stage::new(UNARY_ENTRY, 1)
+                .dispatch(dispatch)
+                .module(&double)],
+        },
+    ];
+
+    let results = run_concurrent(&gpu, jobs);
+    let ids: Vec<JobId> = results.iter().map(|(id, _, _)| *id).collect();
+    assert_eq!(ids, [0, 1, 2]);
+    assert_eq!(results[0].1, add_two_vec(&a, &b, a.len()));
+    assert_eq!(results[1].1, [sum_vec(&a, a.len())]);
+    assert_eq!(
+        results[2].1,
+        b.iter().map(|&x| x.wrapping_mul(2)).collect::<Vec<u32>>()
+    );
+
+    let timestamps = gpu
+        .device
+        .features()
+        .contains(wgpu::Features::TIMESTAMP_QUERY);
+    for (id, _, time) in &results {
+        assert_eq!(time.is_some(), timestamps, "job {}", id);
+    }
+}
+
+#[test]
+fn the_device_memory_hint_is_at_least_a_megabyte() {
+    let Some(gpu) = gpu(Op::Add.shader_file()) else {
+        return;
+    };
+    if let Some(bytes) = gpu.device_memory_hint() {
+        assert!(bytes >= 1 << 20, "{} bytes", bytes);
+    }
+}
+
+#[test]
+fn a_strided_map_agrees_with_the_cpu() {
+    let Some(gpu) = gpu(Op::Add.shader_file()) else {
+        return;
+    };
+    let data = generate_data(1001, 55);
+    assert_eq!(
+        gpu_strided_map(&gpu, &data, 2, 500).unwrap(),
+        strided_map_vec(&data, 2, 500)
+    );
+    assert!(matches!(
+        gpu_strided_map(&gpu, &data, 2, 501),
+        Err(GpuError::StrideOutOfBounds {
+            stride: 2,
+            count: 501,
+            len: 1001
+        })
+    ));
+}
+
+#[test]
+fn the_result_iterator_yields_the_collected_result() {
+    let Some(gpu) = gpu(Op::Add.shader_file()) else {
+        return;
+    };
+    let a = generate_data(1000, 56);
+    let b = generate_data(1000, 57);
+    let mut bindings = Bindings::initialize_three(vec![0; a.len()], a.clone(), b.clone());
+    let stage = Stage::new("vectorAddition_call", 3).dispatch(gpu.dispatch_for(a.len()).unwrap());
+    let bc = BufCoder::initialize_stages(&gpu, &mut bindings, &[stage]);
+
+    let sum = pollster::block_on(gpu.run_iter(&bc))
+        .unwrap()
+        .fold(0u32, u32::wrapping_add);
+    // The iterator unmaps the buffer once it is dropped, so it can be mapped again
+    let collected = pollster::block_on(gpu.run::<u32>(&bc)).unwrap();
+    assert_eq!(collected, add_two_vec(&a, &b, a.len()));
+    assert_eq!(sum, sum_vec(&collected, collected.len()));
+}
+
+#[test]
+fn run_many_never_has_more_runs_in_flight_than_the_cap() {
+    let Some(gpu) = gpu(Op::Add.shader_file()) else {
+        return;
+    };
+    let increment = gpu.create_module("increment", &gen_unary_kernel("x + 1u"));
+    let inputs: Vec<Vec<u32>> = (0..10).map(|i| generate_data(1000, 58 + i)).collect();
+    let bindings = inputs
+        .iter()
+        .map(|data| Bindings::initialize_one(data.clone()))
+        .collect();
+    let stage = Stage::new(UNARY_ENTRY, 1)
+        .dispatch(gpu.dispatch_for(1000).unwrap())
+        .module(&increment);
+
+    let mut most = 0;
+    let results = gpu.run_many_observed(stage, bindings, 3, |in_flight| most = most.max(in_flight));
+    assert_eq!(most, 3);
+    assert_eq!(results.len(), inputs.len());
+    for (result, data) in results.iter().zip(&inputs) {
+        assert_eq!(*result, data.iter().map(|&x| x + 1).collect::<Vec<u32>>());
+    }
+}
+
+#[test]
+fn compute_is_supported_wherever_a_context_can_be_created() {
+    // Without any backend to search there is no adapter, which is an error, not a panic
+    let options = GpuOptions {
+        backends: wgpu::Backends::empty(),
+        ..Default::default()
+    };
+    assert!(matches!(
+        pollster::block_on(GpuConsts::initialaze_with(Op::Add.shader_file(), &options)),
+        Err(GpuError::NoAdapter { .. })
+    ));
+
+    let Some(gpu) = gpu(Op::Add.shader_file()) else {
+        return;
+    };
+    assert!(gpu.supports_compute());
+}
+
+#[test]
+fn unpacked_masks_match_the_cpu_up_to_the_last_partial_word() {
+    let Some(gpu) = gpu(Op::Add.shader_file()) else {
+        return;
+    };
+    let data = generate_data(1013, 68);
+    assert_eq!(
+        gpu_mask(&gpu, &data, "maskEven_call"),
+        cpu_mask(&data, |x| x % 2 == 0)
+    );
+    assert_eq!(
+        gpu_mask(&gpu, &data, "maskOdd_call"),
+        cpu_mask(&data, |x| x % 2 == 1)
+    );
+}
+
+#[test]
+fn threaded_sums_agree_with_sum_vec_at_any_thread_count() {
+    let data = generate_data(10_001, 69);
+    let exact: u64 = data.iter().map(|&x| x as u64).sum();
+    for threads in [1, 2, 3, 7, 16] {
+        let sum = threaded_sum_vec(&data, threads);
+        assert_eq!(sum, exact, "{} threads", threads);
+        // `sum_vec` wraps where the `u64` sum doesn't
+        assert_eq!(
+            sum as u32,
+            sum_vec(&data, data.len()),
+            "{} threads",
+            threads
+        );
+    }
+    // More threads than elements leaves some without a chunk
+    assert_eq!(threaded_sum_vec(&[1, 2, 3], 8), 6);
+    assert_eq!(threaded_sum_vec(&[], 4), 0);
+}
+
+#[test]
+fn plans_past_one_dimension_cover_every_workgroup() {
+    let Some(gpu) = gpu(Op::Add.shader_file()) else {
+        return;
+    };
+    let max = gpu.device.limits().max_compute_workgroups_per_dimension as u64;
+    let plan = gpu.plan_dispatch(max).unwrap();
+    assert_eq!((plan.workgroups, plan.dimensions), ((max as u32, 1, 1), 1));
+
+    for (needed, dimensions) in [(max + 1, 2), (max * max, 2), (max * max + 1, 3)] {
+        let plan = gpu.plan_dispatch(needed).unwrap();
+        let (x, y, z) = plan.workgroups;
+        assert_eq!(plan.dimensions, dimensions, "{} workgroups", needed);
+        assert!(x as u64 <= max && y as u64 <= max && z as u64 <= max);
+        assert!(
+            x as u64 * y as u64 * z as u64 >= needed,
+            "{} workgroups",
+            needed
+        );
+    }
+    assert!(matches!(
+        gpu.plan_dispatch(max.pow(3) + 1),
+        Err(GpuError::LimitExceeded { .. })
+    ));
+}
+
+#[test]
+fn kernels_with_fragment_visible_bindings_still_compute() {
+    let Some(gpu) = gpu(Op::Add.shader_file()) else {
+        return;
+    };
+    // Only the read-only inputs, writable storage in fragment shaders needs device support
+    let shared = wgpu::ShaderStages::COMPUTE | wgpu::ShaderStages::FRAGMENT;
+    let kernel = gpu
+        .compile_kernel_with_visibility(
+            "vectorAddition_call",
+            &[wgpu::ShaderStages::COMPUTE, shared, shared],
+        )
+        .unwrap();
+    let a = generate_data(1000, 70);
+    let b = generate_data(1000, 71);
+    let mut bindings = Bindings::initialize_three(vec![0; a.len()], a.clone(), b.clone());
+    let stage = Stage::from_kernel(&kernel, 3).dispatch(gpu.dispatch_for(a.len()).unwrap());
+    let bc = BufCoder::initialize_stages(&gpu, &mut bindings, &[stage]);
+
+    assert_eq!(
+        pollster::block_on(gpu.run::<u32>(&bc)).unwrap(),
+        add_two_vec(&a, &b, a.len())
+    );
+}
+
+#[test]
+fn compared_reductions_agree_at_every_size() {
+    let Some(gpu) = gpu(Op::Add.shader_file()) else {
+        return;
+    };
+    let sizes = [0, 1, 1000, 100_000];
+    let comparisons = compare_reductions(&gpu, &sizes);
+    assert_eq!(comparisons.len(), sizes.len());
+    for comparison in &comparisons {
+        assert!(comparison.agree(), "{:?}", comparison);
+        let data = generate_data(comparison.len, 0);
+        assert_eq!(
+            comparison.cpu_sum,
+            data.iter().map(|&x| x as u64).sum::<u64>()
+        );
+    }
+}
+
+#[test]
+fn run_into_slice_reuses_the_capacity_of_the_vector() {
+    let Some(gpu) = gpu(Op::Add.shader_file()) else {
+        return;
+    };
+    let increment = gpu.create_module("increment", &gen_unary_kernel("x + 1u"));
+    let mut out = Vec::new();
+    let mut allocation = None;
+    for seed in [72, 73] {
+        let data = generate_data(1000, seed);
+        let mut bindings = Bindings::initialize_one(data.clone());
+        let stage = Stage::new(UNARY_ENTRY, 1)
+            .dispatch(gpu.dispatch_for(1000).unwrap())
+            .module(&increment);
+        let bc = BufCoder::initialize_stages(&gpu, &mut bindings, &[stage]);
+
+        pollster::block_on(gpu.run_into_slice(&bc, &mut out));
+        assert_eq!(out, data.iter().map(|&x| x + 1).collect::<Vec<u32>>());
+        // The second run of the same size writes into the allocation of the first
+        assert_eq!(*allocation.get_or_insert(out.as_ptr()), out.as_ptr());
+    }
+}
+
+#[test]
+fn a_grid_stride_sum_of_four_per_thread_equals_the_cpu_sum() {
+    let Some(gpu) = gpu(Op::Sum.shader_file()) else {
+        return;
+    };
+    for len in [1, 4, 1023, 100_000] {
+        let data = generate_data(len, 76);
+        assert_eq!(
+            gpu_grid_stride_sum(&gpu, &data, 4).unwrap(),
+            sum_vec(&data, len)
+        );
+    }
+}
+
+#[test]
+fn the_diff_report_lists_injected_mismatches_up_to_the_limit() {
+    let cpu = generate_data(100, 77);
+    let mut gpu = cpu.clone();
+    for index in [3, 40, 41, 99] {
+        gpu[index] = gpu[index].wrapping_add(5);
+    }
+
+    let report = diff_report(&cpu, &gpu, 2);
+    assert!(report.contains(&format!("| 3 | {} | {} | 5 |", cpu[3], gpu[3])));
+    assert!(report.contains(&format!("| 40 | {} | {} | 5 |", cpu[40], gpu[40])));
+    assert!(!report.contains("| 41 |"));
+    assert!(!report.contains("| 99 |"));
+    assert!(report.ends_with("4 mismatches, 2 shown"));
+
+    assert_eq!(diff_report(&cpu, &cpu, 2), "0 mismatches, 0 shown");
+}
+
+#[test]
+fn timing_stats_of_fixed_samples_are_exact() {
+    let millis = |ms: &[u64]| {
+        ms.iter()
+            .map(|&ms| Duration::from_millis(ms))
+            .collect::<Vec<_>>()
+    };
+
+    let stats = TimingStats::from_samples(&millis(&[9, 1, 5, 3, 7])).unwrap();
+    assert_eq!(stats.min, Duration::from_millis(1));
+    assert_eq!(stats.median, Duration::from_millis(5));
+    assert!((stats.mean.as_secs_f64() - 0.005).abs() < 1e-9);
+    // Population variance of 1, 3, 5, 7, 9 ms is 8 ms²
+    assert!((stats.std_dev.as_secs_f64() - 8e-6f64.sqrt()).abs() < 1e-9);
+
+    let even = TimingStats::from_samples(&millis(&[4, 2, 8, 6])).unwrap();
+    assert_eq!(even.median, Duration::from_millis(5));
+    assert!(TimingStats::from_samples(&[]).is_none());
+}
+
+#[test]
+fn the_atomic_f32_sum_is_close_to_the_kahan_sum() {
+    let Some(gpu) = gpu(Op::Sum.shader_file()) else {
+        return;
+    };
+    let data: Vec<f32> = generate_data(10_000, 79)
+        .iter()
+        .map(|&x| (x % 1000) as f32 / 7.0)
+        .collect();
+
+    let expected = kahan_sum_f32(&data);
+    let sum = gpu_atomic_sum_f32(&gpu, &data);
+    // The order of the additions varies, so only the rounding errors are allowed to differ
+    assert!(
+        (sum - expected).abs() <= expected.abs() * 1e-4,
+        "{} vs {}",
+        sum,
+        expected
+    );
+}
+
+#[test]
+fn every_clear_strategy_leaves_only_zeros() {
+    let Some(gpu) = gpu(Op::Add.shader_file()) else {
+        return;
+    };
+    for strategy in [
+        ClearStrategy::ClearBuffer,
+        ClearStrategy::Kernel,
+        ClearStrategy::InitZeros,
+    ] {
+        let (contents, _) = measure_clear(&gpu, 100_000, strategy);
+        assert_eq!(contents.len(), 100_000, "{:?}", strategy);
+        assert!(contents.iter().all(|&x| x == 0), "{:?}", strategy);
+    }
+}
+
+#[test]
+fn vector_addition_has_an_intensity_of_a_twelfth() {
+    let n = 1_000_000;
+    let profile = KernelProfile {
+        bytes: 12 * n,
+        flops: n,
+    };
+    assert!((arithmetic_intensity(&profile) - 1.0 / 12.0).abs() < 1e-12);
+    // Memory bound on a 1000 GFLOP/s, 120 GB/s device: 10 GFLOP/s at most
+    assert!((attainable_gflops(&profile, 1000.0, 120.0) - 10.0).abs() < 1e-9);
+    assert!((roofline_efficiency(&profile, 5.0, 1000.0, 120.0) - 0.5).abs() < 1e-9);
+    assert_eq!(
+        arithmetic_intensity(&KernelProfile { bytes: 0, flops: 1 }),
+        0.0
+    );
+}
+
+#[test]
+fn a_gather_reads_f32_values_through_u32_indices() {
+    let Some(gpu) = gpu(Op::Add.shader_file()) else {
+        return;
+    };
+    let values: Vec<f32> = (0..1000).map(|x| x as f32 * 0.5).collect();
+    let indices: Vec<u32> = generate_data(5000, 82)
+        .iter()
+        .map(|&x| x * 7 % 1000)
+        .collect();
+
+    let gathered = gpu_gather_f32(&gpu, &indices, &values).unwrap();
+    let expected: Vec<f32> = indices.iter().map(|&i| values[i as usize]).collect();
+    assert_eq!(gathered, expected);
+    assert!(gpu_gather_f32(&gpu, &[1000], &values).is_none());
+}
+
+#[test]
+fn cancelled_work_is_neither_submitted_nor_waited_for() {
+    let Some(gpu) = gpu(Op::Add.shader_file()) else {
+        return;
+    };
+    let stage = || Stage::new(Op::Add.entry_point(), 3).dispatch(gpu.dispatch_for(1000).unwrap());
+    let a = generate_data(1000, 83);
+    let b = generate_data(1000, 84);
+    let mut bindings = Bindings::initialize_three(vec![0; 1000], a.clone(), b.clone());
+
+    let token = CancelToken::new();
+    token.clone().cancel();
+    assert!(matches!(
+        BufCoder::initialize_cancellable(&gpu, &mut bindings, &[stage()], &token),
+        Err(GpuError::Cancelled)
+    ));
+
+    let bc = BufCoder::initialize_cancellable(&gpu, &mut bindings, &[stage()], &CancelToken::new())
+        .unwrap();
+    assert!(matches!(
+        pollster::block_on(gpu.run_cancellable::<u32>(&bc, &token)),
+        Err(GpuError::Cancelled)
+    ));
+    // A token that is never cancelled waits for the result
+    let result = pollster::block_on(gpu.run_cancellable::<u32>(&bc, &CancelToken::new())).unwrap();
+    assert_eq!(result, add_two_vec(&a, &b, 1000));
+}
+
+#[test]
+#[cfg(feature = "trace-resources")]
+fn created_resources_are_labeled_after_the_prefix() {
+    let options = GpuOptions {
+        label_prefix: Some("bench42".to_string()),
+        ..Default::default()
+    };
+    let Ok(gpu) = pollster::block_on(GpuConsts::initialaze_with("src/grid_func.wgsl", &options))
+    else {
+        return;
+    };
+    let events = CapturedEvents::default();
+    tracing::subscriber::with_default(events.clone(), || {
+        let mut bindings = Bindings::initialize_one(vec![0; 200]);
+        let bc = BufCoder::initialize_stages(
+            &gpu,
+            &mut bindings,
+            &[Stage::new("markCells2d_call", 1).dispatch_2d(20, 10)],
+        );
+        pollster::block_on(gpu.run::<u32>(&bc)).unwrap();
+    });
+
+    let buffers = events.labels("created", "buffer");
+    // Timestamp buffers too, where the device supports them
+    assert!(
+        buffers.iter().all(|label| label.starts_with("bench42-")),
+        "{:?}",
+        buffers
+    );
+    for label in ["storage-0", "storage-1", "storage-2", "storage-3", "params"] {
+        assert!(
+            buffers.contains(&format!("bench42-{}", label)),
+            "{} in {:?}",
+            label,
+            buffers
+        );
+    }
+    assert_eq!(
+        events.labels("created", "bind group"),
+        [
+            "bench42-bind-group-markCells2d_call",
+            "bench42-params-bind-group-markCells2d_call"
+        ]
+    );
+    assert_eq!(
+        events.labels("created", "pipeline"),
+        ["bench42-pipeline-markCells2d_call"]
+    );
+}
+
+#[test]
+fn the_largest_adapter_is_only_a_software_one_when_allowed() {
+    let options = GpuOptions::default();
+    if let Ok(gpu) = pollster::block_on(GpuConsts::initialaze_largest_memory(
+        Op::Add.shader_file(),
+        &options,
+        false,
+    )) {
+        assert_ne!(gpu.adapter_info().device_type, wgpu::DeviceType::Cpu);
+    }
+    // Any adapter at all is a candidate then, software ones included
+    if gpu(Op::Add.shader_file()).is_some() {
+        pollster::block_on(GpuConsts::initialaze_largest_memory(
+            Op::Add.shader_file(),
+            &options,
+            true,
+        ))
+        .unwrap();
+    }
+}
+
+#[test]
+fn a_prepared_dispatch_runs_twice_with_new_contents() {
+    let Some(gpu) = gpu(Op::Sum.shader_file()) else {
+        return;
+    };
+    let data = generate_data(1000, 87);
+    let sum = sum_vec(&data, data.len());
+    let bindings = Bindings::initialize_two(vec![0], data);
+    let prepared = gpu.prepare_dispatch(&bindings, Op::Sum.entry_point(), 2, (4, 1, 1));
+
+    // Binding 0 is the accumulator, each run starts it from the uploaded value
+    assert_eq!(prepared.run(&gpu, &[0]), [sum]);
+    assert_eq!(prepared.run(&gpu, &[1000]), [sum.wrapping_add(1000)]);
+}
+
+#[test]
+fn gpu_saxpy_agrees_with_the_cpu_within_float_tolerance() {
+    let Some(gpu) = gpu(Op::Add.shader_file()) else {
+        return;
+    };
+    let x: Vec<f32> = generate_data(10_000, 88)
+        .iter()
+        .map(|&v| v as f32 / 3.0)
+        .collect();
+    let y: Vec<f32> = generate_data(10_000, 89)
+        .iter()
+        .map(|&v| v as f32 / 7.0)
+        .collect();
+
+    let a = -1.37;
+    let result = gpu_saxpy(&gpu, a, &x, &y).unwrap();
+    // The GPU may fuse the multiply and add and round once instead of twice
+    assert!(Tolerance::new(1e-5, 1e-6).all_close(&result, &saxpy(a, &x, &y)));
+    assert!(gpu_saxpy(&gpu, a, &x, &y[1..]).is_none());
+}
+
+#[test]
+fn a_cold_start_takes_a_positive_time() {
+    // Without an adapter there is nothing to time
+    if let Ok(time) = measure_init_time(Op::Add.shader_file()) {
+        assert!(time > Duration::ZERO);
+    }
+}
+
+#[test]
+fn the_scalar_readback_is_element_zero_of_the_full_one() {
+    let Some(gpu) = gpu(Op::Sum.shader_file()) else {
+        return;
+    };
+    let data = generate_data(100_000, 90);
+    let mut bindings = Bindings::initialize_two(vec![0; 4], data.clone());
+    let stage =
+        Stage::new(Op::Sum.entry_point(), 2).dispatch(gpu.dispatch_for(data.len()).unwrap());
+    let bc = BufCoder::initialize_stages(&gpu, &mut bindings, &[stage]);
+
+    let full = pollster::block_on(gpu.run::<u32>(&bc)).unwrap();
+    assert_eq!(full[0], sum_vec(&data, data.len()));
+    assert_eq!(pollster::block_on(gpu.read_scalar(&bc)).unwrap(), full[0]);
+
+    // Without readback only the 4 bytes of the scalar are copied
+    let mut bindings = Bindings::initialize_two(vec![0; 4], data.clone());
+    let bc = BufCoder::initialize_stages_without_readback(&gpu, &mut bindings, &[stage]);
+    assert!(bc.staging_buffer.is_none());
+    assert_eq!(gpu.copy_scalar(&bc).unwrap().size(), 4);
+    assert_eq!(pollster::block_on(gpu.read_scalar(&bc)).unwrap(), full[0]);
+}
+
+#[test]
+fn a_declared_output_shorter_than_the_buffer_is_flagged() {
+    let Some(gpu) = gpu(Op::Add.shader_file()) else {
+        return;
+    };
+    let a = generate_data(1000, 91);
+    let b = generate_data(1000, 92);
+    let mut bindings = Bindings::initialize_three(vec![0; 1000], a.clone(), b.clone());
+    let stage = Stage::new(Op::Add.entry_point(), 3).dispatch(gpu.dispatch_for(1000).unwrap());
+    let bc = BufCoder::initialize_stages(&gpu, &mut bindings, &[stage]);
+
+    assert!(matches!(
+        pollster::block_on(gpu.run_expecting::<u32>(&bc, 500)),
+        Err(GpuError::OutputLengthMismatch {
+            expected_bytes: 2000,
+            actual_bytes: 4000
+        })
+    ));
+    assert_eq!(
+        pollster::block_on(gpu.run_expecting::<u32>(&bc, 1000)).unwrap(),
+        add_two_vec(&a, &b, 1000)
+    );
+}
+
+#[test]
+fn the_capability_report_describes_the_created_device() {
+    let Some(gpu) = gpu(Op::Add.shader_file()) else {
+        return;
+    };
+    let report = gpu.capability_report();
+    assert_eq!(report.info.name, gpu.adapter_info().name);
+    assert_eq!(report.features, gpu.device.features());
+    assert_eq!(
+        report.limits.max_buffer_size,
+        gpu.device.limits().max_buffer_size
+    );
+    assert_eq!(report.readback_path, gpu.readback_path());
+
+    let text = report.to_string();
+    assert!(text.starts_with(&format!("adapter: {}\n", report.info.name)));
+    assert!(text.contains("readback path: "));
+}
+
+#[test]
+fn the_wide_sum_carries_past_u32() {
+    let Some(gpu) = gpu(Op::Sum.shader_file()) else {
+        return;
+    };
+    let data = vec![u32::MAX - 3; 100_000];
+    let expected: u64 = data.iter().map(|&x| x as u64).sum();
+    assert_eq!(gpu_wide_sum(&gpu, &data), expected);
+    assert_eq!(gpu_wide_sum(&gpu, &[]), 0);
+}
+
+#[test]
+fn the_naive_f32_sum_drifts_furthest_from_the_f64_sum() {
+    // Ones are below half an ulp of 1e8 in f32, so the naive sum drops every one of them
+    let mut data = vec![1e8f32];
+    data.extend(std::iter::repeat_n(1.0, 10_000));
+
+    let comparison = compare_precision(None, &data);
+    assert_eq!(comparison.truth, 1e8 + 10_000.0);
+    assert_eq!(comparison.naive_error, 10_000.0);
+    assert!(comparison.kahan_error < comparison.naive_error);
+    assert!(comparison.kahan_error <= 8.0, "{}", comparison.kahan_error);
+    assert!(comparison.gpu_error.is_none());
+}
+
+#[test]
+fn the_shared_gpu_is_one_instance_that_dispatches_from_any_thread() {
+    if gpu(Op::Add.shader_file()).is_none() {
+        return;
+    }
+    let data = generate_data(1000, 95);
+
+    let handles: Vec<_> = (0..2)
+        .map(|_| {
+            let data = data.clone();
+            std::thread::spawn(move || {
+                let gpu = shared_gpu();
+                (
+                    gpu as *const GpuConsts as usize,
+                    Op::Add.run_gpu(gpu, &data),
+                )
+            })
+        })
+        .collect();
+    let results: Vec<_> = handles.into_iter().map(|h| h.join().unwrap()).collect();
+
+    assert_eq!(results[0].0, results[1].0);
+    for (_, output) in &results {
+        assert_eq!(*output, Op::Add.run_cpu(&data));
+    }
+}
+
+#[test]
+fn a_min_reduction_seeded_with_u32_max_finds_the_minimum() {
+    let Some(gpu) = gpu(Op::Add.shader_file()) else {
+        return;
+    };
+    let module = gpu.create_module("atomic", include_str!("atomic_func.wgsl"));
+    let data: Vec<u32> = generate_data(10_000, 96).iter().map(|&x| x + 7).collect();
+    let min = |mut bindings: Bindings| {
+        bindings.set_binding(1, &data);
+        let stage = Stage::new("atomicMin_call", 2)
+            .dispatch(gpu.dispatch_for(data.len()).unwrap())
+            .module(&module);
+        let bc = BufCoder::initialize_stages(&gpu, &mut bindings, &[stage]);
+        pollster::block_on(gpu.run::<u32>(&bc)).unwrap()[0]
+    };
+
+    assert_eq!(
+        min(Bindings::initialize_seeded(1, u32::MAX)),
+        *data.iter().min().unwrap()
+    );
+    // Starting from zero, nothing is ever smaller
+    assert_eq!(min(Bindings::initialize_generator(1)), 0);
+}
+
+#[test]
+fn gpu_argmax_keeps_the_lowest_index_of_a_tie() {
+    let Some(gpu) = gpu(Op::Add.shader_file()) else {
+        return;
+    };
+    let mut data = generate_data(10_000, 97);
+    data[1234] = 500;
+    data[8765] = 500;
+
+    // `max_by_key` finds the same value, but the last of equal maxima
+    let (last, &max) = data.iter().enumerate().max_by_key(|&(_, x)| x).unwrap();
+    assert_eq!(last, 8765);
+    assert_eq!(cpu_argmax(&data), Some((max, 1234)));
+    assert_eq!(gpu_argmax(&gpu, &data), cpu_argmax(&data));
+
+    let distinct: Vec<u32> = (0..1000).map(|x| x * 7919 % 1000).collect();
+    assert_eq!(gpu_argmax(&gpu, &distinct), cpu_argmax(&distinct));
+    assert_eq!(gpu_argmax(&gpu, &[]), None);
+}
+
+#[test]
+fn a_generated_kernel_agrees_with_the_same_closure_on_the_cpu() {
+    let Some(gpu) = gpu(Op::Add.shader_file()) else {
+        return;
+    };
+    let data = generate_data(100_000, 99);
+    let expected: Vec<u32> = data.iter().map(|&x| x * x + 1).collect();
+
+    assert_eq!(gpu_unary(&gpu, "x * x + 1u", &data), expected);
+    assert!(gen_unary_kernel("x * x + 1u").contains("data[idx] = x * x + 1u;"));
+}
+
+#[test]
+#[cfg(feature = "trace-resources")]
+fn dropping_an_unread_bufcoder_warns() {
+    let Some(gpu) = gpu(Op::Add.shader_file()) else {
+        return;
+    };
+    let increment = gpu.create_module("increment", &gen_unary_kernel("x + 1u"));
+    let dispatch = |finish: &dyn Fn(BufCoder)| {
+        let events = CapturedEvents::default();
+        tracing::subscriber::with_default(events.clone(), || {
+            let mut bindings = Bindings::initialize_one(vec![1; 1000]);
+            let stage = Stage::new(UNARY_ENTRY, 1)
+                .dispatch(gpu.dispatch_for(1000).unwrap())
+                .module(&increment);
+            finish(BufCoder::initialize_stages(&gpu, &mut bindings, &[stage]));
+        });
+        let captured = events.0.lock().unwrap();
+        captured
+            .iter()
+            .filter(|(level, _)| *level == tracing::Level::WARN)
+            .count()
+    };
+
+    assert_eq!(dispatch(&drop), 1);
+    assert_eq!(
+        dispatch(&|bc| {
+            pollster::block_on(gpu.run::<u32>(&bc)).unwrap();
+        }),
+        0
+    );
+    assert_eq!(dispatch(&BufCoder::discard), 0);
+}
+
+#[test]
+fn a_queue_preference_falls_back_to_the_default_queue() {
+    let options = GpuOptions {
+        queue_preference: QueuePreference::HighPriority,
+        ..Default::default()
+    };
+    let Ok(gpu) = pollster::block_on(GpuConsts::initialaze_with(Op::Add.shader_file(), &options))
+    else {
+        return;
+    };
+
+    let queue = gpu.queue_info();
+    assert_eq!(queue.requested, QueuePreference::HighPriority);
+    assert!(!queue.applied);
+    assert_eq!(gpu.capability_report().queue, queue);
+    // The fallback queue runs work like any other
+    let data = generate_data(1000, 39);
+    assert_eq!(Op::Add.run_gpu(&gpu, &data), Op::Add.run_cpu(&data));
+}
+
+#[test]
+fn the_same_module_source_is_compiled_once() {
+    let Some(gpu) = gpu(Op::Add.shader_file()) else {
+        return;
+    };
+    let source = gen_unary_kernel("x + 1u");
+    let first = gpu.create_module("increment", &source);
+    let second = gpu.create_module("increment again", &source);
+    assert!(Arc::ptr_eq(&first, &second));
+    assert!(!Arc::ptr_eq(
+        &first,
+        &gpu.create_module("double", &gen_unary_kernel("x * 2u"))
+    ));
+
+    // The wrappers compiling their kernel on every call reuse it too
+    let data = generate_data(100, 55);
+    for _ in 0..3 {
+        assert_eq!(
+            gpu_unary(&gpu, "x + 1u", &data),
+            data.iter().map(|&x| x + 1).collect::<Vec<u32>>()
+        );
+    }
+    assert!(Arc::ptr_eq(&first, &gpu.create_module("unary", &source)));
+}
+
+#[test]
+fn bench_op_reports_every_run_and_whether_all_agreed() {
+    let data = generate_data(1000, 78);
+
+    let cpu_only = bench_op(None, Op::Add, &data, 3);
+    assert_eq!((cpu_only.op, cpu_only.len), (Op::Add, 1000));
+    assert!(cpu_only.cpu.min <= cpu_only.cpu.median);
+    assert!(cpu_only.gpu.is_none());
+    assert!(cpu_only.agree.is_none());
+
+    let Some(gpu) = gpu(Op::Add.shader_file()) else {
+        return;
+    };
+    let result = bench_op(Some(&gpu), Op::Add, &data, 3);
+    assert!(result.gpu.is_some());
+    assert_eq!(result.agree, Some(true));
+}
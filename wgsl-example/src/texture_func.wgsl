@@ -0,0 +1,12 @@
+@group(0) @binding(0) var output: texture_storage_2d<r32uint, write>;
+@group(0) @binding(1) var input_a: texture_2d<u32>;
+
+@compute @workgroup_size(16, 16)
+fn textureIncrement_call(@builtin(global_invocation_id) global_id: vec3u) {
+  let dims = textureDimensions(input_a);
+  if global_id.x < dims.x && global_id.y < dims.y {
+    let texel = vec2<i32>(global_id.xy);
+    let value = textureLoad(input_a, texel, 0).x;
+    textureStore(output, texel, vec4<u32>(value + 1u, 0u, 0u, 0u));
+  }
+}
@@ -0,0 +1,37 @@
+@group(0) @binding(0) var<storage, read_write> output: array<atomic<u32>>;
+@group(0) @binding(1) var<storage, read> input_a: array<u32>;
+
+var<workgroup> partial: array<u32, 256>;
+
+// See `flat_index` of `vec_func.wgsl`.
+fn flat_index(global_id: vec3u, num_workgroups: vec3u) -> u32 {
+  return global_id.x + (global_id.y + global_id.z * num_workgroups.y) * num_workgroups.x * 256u;
+}
+
+// Every workgroup halves its 256 values in shared memory until one is left,
+// then adds it to output[0]. Wraps on overflow like the other `u32` sums.
+@compute @workgroup_size(256)
+fn treeSum_call(
+  @builtin(global_invocation_id) global_id: vec3u,
+  @builtin(num_workgroups) num_workgroups: vec3u,
+  @builtin(local_invocation_index) local: u32,
+) {
+  let idx = flat_index(global_id, num_workgroups);
+  var value = 0u;
+  if idx < arrayLength(&input_a) {
+    value = input_a[idx];
+  }
+  partial[local] = value;
+  workgroupBarrier();
+
+  for (var stride = 128u; stride > 0u; stride = stride / 2u) {
+    if local < stride {
+      partial[local] += partial[local + stride];
+    }
+    workgroupBarrier();
+  }
+
+  if local == 0u {
+    atomicAdd(&output[0], partial[0]);
+  }
+}
@@ -1,29 +1,79 @@
 @group(0) @binding(0) var<storage, read_write> output: array<u32>;
 @group(0) @binding(1) var<storage, read> input_a: array<u32>;
 @group(0) @binding(2) var<storage, read> input_b: array<u32>;
+@group(0) @binding(3) var<storage, read_write> overflow: array<u32>;
 
-fn vectorAddition(global_id: vec3u) {
-  let idx = global_id.x;
+// Index of the element of an invocation of a 256-wide workgroup. `GpuConsts::plan_dispatch`
+// continues the grid along y and z once x is at its limit, row after row of x workgroups.
+fn flat_index(global_id: vec3u, num_workgroups: vec3u) -> u32 {
+  return global_id.x + (global_id.y + global_id.z * num_workgroups.y) * num_workgroups.x * 256u;
+}
+
+fn vectorAddition(idx: u32) {
   if idx < arrayLength(&output) {
     output[idx] = input_a[idx] + input_b[idx];
   }
 }
 
+// Like `vectorAddition`, but sets overflow[0] to 1 when any lane wraps around.
+// WGSL has no checked add, `a > MAX - b` is the same test without overflowing itself.
+fn vectorAdditionChecked(idx: u32) {
+  if idx < arrayLength(&output) {
+    let a = input_a[idx];
+    let b = input_b[idx];
+    if a > 0xFFFFFFFFu - b {
+      overflow[0] = 1u;
+    }
+    output[idx] = a + b;
+  }
+}
+
 @compute @workgroup_size(256)
-fn vectorAddition_call(@builtin(global_invocation_id) global_id: vec3u) {
-  vectorAddition(global_id);
+fn vectorAdditionChecked_call(
+  @builtin(global_invocation_id) global_id: vec3u,
+  @builtin(num_workgroups) num_workgroups: vec3u,
+) {
+  vectorAdditionChecked(flat_index(global_id, num_workgroups));
 }
 
 @compute @workgroup_size(256)
-fn batch1000_vectorAddition_call(@builtin(global_invocation_id) global_id: vec3u) {
+fn vectorAddition_call(
+  @builtin(global_invocation_id) global_id: vec3u,
+  @builtin(num_workgroups) num_workgroups: vec3u,
+) {
+  vectorAddition(flat_index(global_id, num_workgroups));
+}
+
+@compute @workgroup_size(256)
+fn batch1000_vectorAddition_call(
+  @builtin(global_invocation_id) global_id: vec3u,
+  @builtin(num_workgroups) num_workgroups: vec3u,
+) {
   for (var i: u32 = 0u; i < 1000u; i = i + 1u) {
-    vectorAddition(global_id);
+    vectorAddition(flat_index(global_id, num_workgroups));
   }
 }
 
 @compute @workgroup_size(256)
-fn batch100000_vectorAddition_call(@builtin(global_invocation_id) global_id: vec3u) {
+fn batch100000_vectorAddition_call(
+  @builtin(global_invocation_id) global_id: vec3u,
+  @builtin(num_workgroups) num_workgroups: vec3u,
+) {
   for (var i: u32 = 0u; i < 10000u; i = i + 1u) {
-    vectorAddition(global_id);
+    vectorAddition(flat_index(global_id, num_workgroups));
   }
 }
+
+
+// Writes the `[x, y, z]` workgroup counts needed to cover `input_a` into `output`,
+// so `vectorAddition_call` can be dispatched indirectly without a host round trip.
+// Past the 65535 workgroups every device allows along x, they are split into rows like
+// `GpuConsts::plan_dispatch` does.
+@compute @workgroup_size(1)
+fn vectorAddition_dispatch_size_call() {
+  let count = (arrayLength(&input_a) + 255u) / 256u;
+  let rows = (count + 65534u) / 65535u;
+  output[0] = (count + rows - 1u) / rows;
+  output[1] = rows;
+  output[2] = 1u;
+}
@@ -0,0 +1,3 @@
+// Does nothing; dispatched once so driver and queue initialization happen before timing.
+@compute @workgroup_size(1)
+fn warmup_call() {}
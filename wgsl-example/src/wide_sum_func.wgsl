@@ -0,0 +1,28 @@
+// The 64-bit sum is kept as two words: output[0] holds the low bits,
+// output[1] counts how many times the low word wrapped around.
+@group(0) @binding(0) var<storage, read_write> output: array<atomic<u32>>;
+@group(0) @binding(1) var<storage, read> input_a: array<u32>;
+
+// See `flat_index` of `vec_func.wgsl`.
+fn flat_index(global_id: vec3u, num_workgroups: vec3u) -> u32 {
+  return global_id.x + (global_id.y + global_id.z * num_workgroups.y) * num_workgroups.x * 256u;
+}
+
+fn wideVectorSum(idx: u32) {
+  if idx < arrayLength(&input_a) {
+    let value = input_a[idx];
+    let old = atomicAdd(&output[0], value);
+    // The add is atomic, so every wrap of the low word is seen by exactly one invocation
+    if old + value < old {
+      atomicAdd(&output[1], 1u);
+    }
+  }
+}
+
+@compute @workgroup_size(256)
+fn wideVectorSum_call(
+  @builtin(global_invocation_id) global_id: vec3u,
+  @builtin(num_workgroups) num_workgroups: vec3u,
+) {
+  wideVectorSum(flat_index(global_id, num_workgroups));
+}
@@ -0,0 +1,47 @@
+// Trailing sliding-window sum: output[i] = input[i + 1 - window] + ... + input[i], wrapping.
+// The first window - 1 outputs have partial windows, the elements before input[0] count as 0.
+struct Params {
+  len: u32,
+  window: u32,
+  _pad0: u32,
+  _pad1: u32,
+}
+
+@group(0) @binding(0) var<storage, read_write> output: array<u32>;
+@group(0) @binding(1) var<storage, read> input: array<u32>;
+@group(1) @binding(0) var<uniform> params: Params;
+
+// The 256 elements of the workgroup, preceded by the window - 1 elements before them
+var<workgroup> tile: array<u32, 512>;
+
+@compute @workgroup_size(256)
+fn windowSum_call(
+  @builtin(workgroup_id) workgroup_id: vec3u,
+  @builtin(num_workgroups) num_workgroups: vec3u,
+  @builtin(local_invocation_index) local: u32,
+) {
+  // Workgroups are numbered row after row of the grid, see `flat_index` of `vec_func.wgsl`
+  let workgroup =
+    workgroup_id.x + (workgroup_id.y + workgroup_id.z * num_workgroups.y) * num_workgroups.x;
+  let base = workgroup * 256u;
+  let halo = params.window - 1u;
+
+  // tile[k] is input[base + k - halo]
+  for (var k = local; k < 256u + halo; k = k + 256u) {
+    var value = 0u;
+    if base + k >= halo && base + k - halo < params.len {
+      value = input[base + k - halo];
+    }
+    tile[k] = value;
+  }
+  workgroupBarrier();
+
+  let idx = base + local;
+  if idx < params.len {
+    var sum = 0u;
+    for (var j = 0u; j < params.window; j = j + 1u) {
+      sum = sum + tile[local + j];
+    }
+    output[idx] = sum;
+  }
+}
@@ -0,0 +1,35 @@
+use std::process::Command;
+
+// Without a GPU the binary runs on the CPU only, so the table is printed on every machine.
+#[test]
+fn bench_prints_one_table_row_per_size() {
+    let output = Command::new(env!("CARGO_BIN_EXE_bench"))
+        .args(["--op", "sum", "--sizes", "10,1k", "--runs", "2"])
+        .output()
+        .unwrap();
+    assert!(output.status.success());
+
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let rows: Vec<&str> = stdout
+        .lines()
+        .filter(|line| line.starts_with("| Sum "))
+        .collect();
+    assert_eq!(rows.len(), 2);
+    assert!(rows[0].starts_with("| Sum | 10 |"));
+    assert!(rows[1].starts_with("| Sum | 1000 |"));
+    // Op, size, the four times and the agreement
+    assert!(rows.iter().all(|row| row.split('|').count() == 9));
+}
+
+#[test]
+fn bench_rejects_unknown_arguments_with_the_usage() {
+    let output = Command::new(env!("CARGO_BIN_EXE_bench"))
+        .args(["--size", "10"])
+        .output()
+        .unwrap();
+
+    assert_eq!(output.status.code(), Some(2));
+    assert!(String::from_utf8(output.stderr)
+        .unwrap()
+        .contains("usage: bench"));
+}
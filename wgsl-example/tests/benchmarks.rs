@@ -16,10 +16,11 @@ fn bench_add_arrays_wgsl(c: &mut Criterion) {
     let mut bindings: Bindings = Bindings::initialize_three(vec1, vec2, vec3);
 
     let gpu = pollster::block_on(GpuConsts::initialaze("src/vec_func.wgsl")).unwrap();
+    gpu.warmup("vectorAddition_call");
     let bc = BufCoder::initialize(&gpu, &mut bindings, "vectorAddition_call", 3);
 
     c.bench_function("add_arrays_wgsl_one", |b| {
-        b.iter(|| pollster::block_on(gpu.run(&bc)))
+        b.iter(|| pollster::block_on(gpu.run::<u32>(&bc)))
     });
 }
 
@@ -37,10 +38,11 @@ fn batch1000_add_arrays_wgsl(c: &mut Criterion) {
     let mut bindings: Bindings = Bindings::initialize_three(vec1, vec2, vec3);
 
     let gpu = pollster::block_on(GpuConsts::initialaze("src/vec_func.wgsl")).unwrap();
+    gpu.warmup("batch1000_vectorAddition_call");
     let bc = BufCoder::initialize(&gpu, &mut bindings, "batch1000_vectorAddition_call", 3);
 
     c.bench_function("batch1000_add_arrays_wgsl", |b| {
-        b.iter(|| pollster::block_on(gpu.run(&bc)))
+        b.iter(|| pollster::block_on(gpu.run::<u32>(&bc)))
     });
 }
 
@@ -58,10 +60,11 @@ fn batch100000_add_arrays_wgsl(c: &mut Criterion) {
     let mut bindings: Bindings = Bindings::initialize_three(vec1, vec2, vec3);
 
     let gpu = pollster::block_on(GpuConsts::initialaze("src/vec_func.wgsl")).unwrap();
+    gpu.warmup("batch100000_vectorAddition_call");
     let bc = BufCoder::initialize(&gpu, &mut bindings, "batch100000_vectorAddition_call", 3);
 
     c.bench_function("batch100000_add_arrays_wgsl", |b| {
-        b.iter(|| pollster::block_on(gpu.run(&bc)))
+        b.iter(|| pollster::block_on(gpu.run::<u32>(&bc)))
     });
 }
 
@@ -73,10 +76,11 @@ fn dry_run_add_arrays_wgsl(c: &mut Criterion) {
     let mut bindings: Bindings = Bindings::initialize_three(vec1, vec2, vec3);
 
     let gpu = pollster::block_on(GpuConsts::initialaze("src/vec_func.wgsl")).unwrap();
+    gpu.warmup("vectorAddition_call");
     let bc = BufCoder::initialize(&gpu, &mut bindings, "vectorAddition_call", 3);
 
     c.bench_function("dry_run_add_arrays_wgsl", |b| {
-        b.iter(|| pollster::block_on(gpu.run(&bc)))
+        b.iter(|| pollster::block_on(gpu.run::<u32>(&bc)))
     });
 }
 
@@ -92,10 +96,11 @@ fn bench_sum_arrays_wgsl(c: &mut Criterion) {
     let mut bindings: Bindings = Bindings::initialize_two(vec1, vec2);
 
     let gpu = pollster::block_on(GpuConsts::initialaze("src/sum_func.wgsl")).unwrap();
+    gpu.warmup("vectorSum_call");
     let bc = BufCoder::initialize(&gpu, &mut bindings, "vectorSum_call", 2);
 
     c.bench_function("sum_arrays_wgsl_one", |b| {
-        b.iter(|| pollster::block_on(gpu.run(&bc)))
+        b.iter(|| pollster::block_on(gpu.run::<u32>(&bc)))
     });
 }
 
@@ -111,10 +116,11 @@ fn batch1000_sum_arrays_wgsl(c: &mut Criterion) {
     let mut bindings: Bindings = Bindings::initialize_two(vec1, vec2);
 
     let gpu = pollster::block_on(GpuConsts::initialaze("src/sum_func.wgsl")).unwrap();
+    gpu.warmup("batch1000_vectorSum_call");
     let bc = BufCoder::initialize(&gpu, &mut bindings, "batch1000_vectorSum_call", 2);
 
     c.bench_function("batch1000_sum_arrays_wgsl", |b| {
-        b.iter(|| pollster::block_on(gpu.run(&bc)))
+        b.iter(|| pollster::block_on(gpu.run::<u32>(&bc)))
     });
 }
 
@@ -130,10 +136,11 @@ fn batch100000_sum_arrays_wgsl(c: &mut Criterion) {
     let mut bindings: Bindings = Bindings::initialize_two(vec1, vec2);
 
     let gpu = pollster::block_on(GpuConsts::initialaze("src/sum_func.wgsl")).unwrap();
+    gpu.warmup("batch100000_vectorSum_call");
     let bc = BufCoder::initialize(&gpu, &mut bindings, "batch100000_vectorSum_call", 2);
 
     c.bench_function("batch100000_sum_arrays_wgsl", |b| {
-        b.iter(|| pollster::block_on(gpu.run(&bc)))
+        b.iter(|| pollster::block_on(gpu.run::<u32>(&bc)))
     });
 }
 
@@ -144,10 +151,11 @@ fn dry_run_sum_arrays_wgsl(c: &mut Criterion) {
     let mut bindings: Bindings = Bindings::initialize_two(vec1, vec2);
 
     let gpu = pollster::block_on(GpuConsts::initialaze("src/sum_func.wgsl")).unwrap();
+    gpu.warmup("vectorSum_call");
     let bc = BufCoder::initialize(&gpu, &mut bindings, "vectorSum_call", 2);
 
     c.bench_function("dry_run_sum_arrays_wgsl", |b| {
-        b.iter(|| pollster::block_on(gpu.run(&bc)))
+        b.iter(|| pollster::block_on(gpu.run::<u32>(&bc)))
     });
 }
 
@@ -163,10 +171,11 @@ fn bench_optimized_sum_arrays_wgsl(c: &mut Criterion) {
     let mut bindings: Bindings = Bindings::initialize_two(vec1, vec2);
 
     let gpu = pollster::block_on(GpuConsts::initialaze("src/optimized_sum_func.wgsl")).unwrap();
+    gpu.warmup("optimized_vectorSum_call");
     let bc = BufCoder::initialize(&gpu, &mut bindings, "optimized_vectorSum_call", 2);
 
     c.bench_function("optimized_sum_arrays_wgsl_one", |b| {
-        b.iter(|| pollster::block_on(gpu.run(&bc)))
+        b.iter(|| pollster::block_on(gpu.run::<u32>(&bc)))
     });
 }
 
@@ -182,10 +191,11 @@ fn batch1000_optimized_sum_arrays_wgsl(c: &mut Criterion) {
     let mut bindings: Bindings = Bindings::initialize_two(vec1, vec2);
 
     let gpu = pollster::block_on(GpuConsts::initialaze("src/optimized_sum_func.wgsl")).unwrap();
+    gpu.warmup("batch1000_optimized_vectorSum_call");
     let bc = BufCoder::initialize(&gpu, &mut bindings, "batch1000_optimized_vectorSum_call", 2);
 
     c.bench_function("batch1000_optimized_sum_arrays_wgsl", |b| {
-        b.iter(|| pollster::block_on(gpu.run(&bc)))
+        b.iter(|| pollster::block_on(gpu.run::<u32>(&bc)))
     });
 }
 
@@ -201,6 +211,7 @@ fn batch100000_optimized_sum_arrays_wgsl(c: &mut Criterion) {
     let mut bindings: Bindings = Bindings::initialize_two(vec1, vec2);
 
     let gpu = pollster::block_on(GpuConsts::initialaze("src/optimized_sum_func.wgsl")).unwrap();
+    gpu.warmup("batch100000_optimized_vectorSum_call");
     let bc = BufCoder::initialize(
         &gpu,
         &mut bindings,
@@ -209,7 +220,7 @@ fn batch100000_optimized_sum_arrays_wgsl(c: &mut Criterion) {
     );
 
     c.bench_function("batch100000_optimized_sum_arrays_wgsl", |b| {
-        b.iter(|| pollster::block_on(gpu.run(&bc)))
+        b.iter(|| pollster::block_on(gpu.run::<u32>(&bc)))
     });
 }
 
@@ -220,10 +231,11 @@ fn dry_run_optimized_sum_arrays_wgsl(c: &mut Criterion) {
     let mut bindings: Bindings = Bindings::initialize_two(vec1, vec2);
 
     let gpu = pollster::block_on(GpuConsts::initialaze("src/optimized_sum_func.wgsl")).unwrap();
+    gpu.warmup("optimized_vectorSum_call");
     let bc = BufCoder::initialize(&gpu, &mut bindings, "optimized_vectorSum_call", 2);
 
     c.bench_function("dry_run_optimized_sum_arrays_wgsl", |b| {
-        b.iter(|| pollster::block_on(gpu.run(&bc)))
+        b.iter(|| pollster::block_on(gpu.run::<u32>(&bc)))
     });
 }
 
@@ -363,6 +375,19 @@ fn batch100000_optimized_sum_arrays_rust(c: &mut Criterion) {
     });
 }
 
+fn recursive_sum_arrays_rust(c: &mut Criterion) {
+    let mut rng = rand::thread_rng();
+    let mut v = vec![0; 1000];
+
+    for j in 0..1000 {
+        v[j] = rng.gen_range(1..=100);
+    }
+
+    c.bench_function("recursive_sum_arrays_rust_one", |b| {
+        b.iter(|| recursive_sum_vec(&v, 0, v.len() - 1))
+    });
+}
+
 fn dry_run_optimized_sum_arrays_rust(c: &mut Criterion) {
     let v = vec![0; 1];
 
@@ -386,7 +411,8 @@ criterion_group! {
   targets =
     bench_add_arrays_rust, batch1000_add_arrays_rust, batch100000_add_arrays_rust, dry_run_add_arrays_rust,
     bench_sum_arrays_rust, batch1000_sum_arrays_rust, batch100000_sum_arrays_rust, dry_run_sum_arrays_rust,
-    bench_optimized_sum_arrays_rust, batch1000_optimized_sum_arrays_rust, batch100000_optimized_sum_arrays_rust, dry_run_optimized_sum_arrays_rust
+    bench_optimized_sum_arrays_rust, batch1000_optimized_sum_arrays_rust, batch100000_optimized_sum_arrays_rust, dry_run_optimized_sum_arrays_rust,
+    recursive_sum_arrays_rust
 }
 
 criterion_main!(wgsl, rust);
@@ -0,0 +1,62 @@
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use wgsl_example::*;
+
+const SIZES: [usize; 3] = [1_000, 100_000, 1_000_000];
+
+fn sum_arrays(c: &mut Criterion) {
+    // GPU benchmarks are left out, not failed, when there is no adapter
+    let gpu = pollster::block_on(GpuConsts::initialaze("src/sum_func.wgsl")).ok();
+    let mut group = c.benchmark_group("sum");
+
+    for size in SIZES {
+        let v = generate_data(size, 0);
+
+        group.bench_with_input(BenchmarkId::new("sum_vec", size), &v, |b, v| {
+            b.iter(|| sum_vec(v, v.len()))
+        });
+        group.bench_with_input(BenchmarkId::new("optimized_sum_vec", size), &v, |b, v| {
+            b.iter(|| optimized_sum_vec(v, 0, v.len() - 1))
+        });
+        #[cfg(feature = "simd")]
+        group.bench_with_input(BenchmarkId::new("simd_sum_vec", size), &v, |b, v| {
+            b.iter(|| simd_sum_vec(v))
+        });
+        if let Some(gpu) = &gpu {
+            group.bench_with_input(BenchmarkId::new("gpu_sum", size), &v, |b, v| {
+                b.iter(|| gpu_reduce_sum(gpu, v))
+            });
+        }
+    }
+
+    group.finish();
+}
+
+fn add_arrays(c: &mut Criterion) {
+    let gpu = pollster::block_on(GpuConsts::initialaze(Op::Add.shader_file())).ok();
+    let mut group = c.benchmark_group("add");
+
+    for size in SIZES {
+        let v = generate_data(size, 0);
+        let v2 = generate_data(size, 1);
+
+        group.bench_with_input(BenchmarkId::new("add_two_vec", size), &v, |b, v| {
+            b.iter(|| add_two_vec(v, &v2, v.len()))
+        });
+        if let Some(gpu) = &gpu {
+            gpu.warmup(Op::Add.entry_point());
+            group.bench_with_input(BenchmarkId::new("gpu_add", size), &v, |b, v| {
+                b.iter(|| Op::Add.run_gpu(gpu, v))
+            });
+        }
+    }
+
+    group.finish();
+}
+
+criterion_group! {
+  name = compare;
+  config = Criterion::default().sample_size(10);
+  targets = sum_arrays, add_arrays
+}
+
+criterion_main!(compare);